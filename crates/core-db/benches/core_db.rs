@@ -0,0 +1,210 @@
+use core_db::{
+    InMemoryEngine, NewDocument, Schema, SchemaField, SchemaType, Value, WriteOperation,
+};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+const TABLE_SIZES: [usize; 2] = [1_000, 100_000];
+
+fn users_schema() -> Schema {
+    let mut fields = BTreeMap::new();
+    for (name, field_type) in [
+        ("name", SchemaType::String),
+        ("age", SchemaType::Number),
+        ("team", SchemaType::String),
+    ] {
+        fields.insert(
+            name.to_string(),
+            SchemaField {
+                required: true,
+                field_type,
+            },
+        );
+    }
+    Schema::with_fields(fields)
+}
+
+fn user_fields(id: usize) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+    fields.insert("name".to_string(), Value::String(format!("user {id}")));
+    fields.insert("age".to_string(), Value::from(id % 90));
+    fields.insert(
+        "team".to_string(),
+        Value::String(format!("team {}", id % 50)),
+    );
+    fields
+}
+
+fn put_user(id: usize) -> WriteOperation {
+    WriteOperation::Put(NewDocument {
+        id: Some(format!("u_{id:08}")),
+        fields: user_fields(id),
+    })
+}
+
+fn seeded_engine(documents: usize, indexes: usize) -> InMemoryEngine {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table creation should work");
+    for (name, field) in [
+        ("by_age", "age"),
+        ("by_team", "team"),
+        ("by_name", "name"),
+        ("by_team_age", "team"),
+        ("by_age_name", "age"),
+    ]
+    .into_iter()
+    .take(indexes)
+    {
+        engine
+            .create_index("users", name, &[field])
+            .expect("index creation should work");
+    }
+    let ops: Vec<WriteOperation> = (0..documents).map(put_user).collect();
+    engine
+        .write_batch("users", &ops)
+        .expect("seed should succeed");
+    engine
+}
+
+fn bench_inserts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    let op = [put_user(usize::MAX / 2)];
+
+    for (label, schema) in [
+        ("with_schema", users_schema()),
+        ("no_schema", Schema::default()),
+    ] {
+        group.bench_function(label, |b| {
+            b.iter_batched(
+                || {
+                    let mut engine = InMemoryEngine::new();
+                    engine
+                        .create_table("users", schema.clone())
+                        .expect("table creation should work");
+                    engine
+                },
+                |mut engine| black_box(engine.write_batch("users", &op).expect("write")),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    for indexes in [0, 1, 5] {
+        let engine = seeded_engine(1_000, indexes);
+        group.bench_with_input(
+            BenchmarkId::new("into_1k_table_with_indexes", indexes),
+            &indexes,
+            |b, _| {
+                b.iter_batched(
+                    || engine.snapshot(),
+                    |mut engine| black_box(engine.write_batch("users", &op).expect("write")),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_reads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read");
+    for size in TABLE_SIZES {
+        let engine = seeded_engine(size, 2);
+        let id = format!("u_{:08}", size / 2);
+        group.bench_with_input(BenchmarkId::new("point_get", size), &size, |b, _| {
+            b.iter(|| black_box(engine.get("users", &id).expect("get")))
+        });
+
+        let age = [Value::from(42)];
+        group.bench_with_input(BenchmarkId::new("index_eq", size), &size, |b, _| {
+            b.iter(|| black_box(engine.query_index("users", "by_age", &age).expect("query")))
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("index_range_limit_20", size),
+            &size,
+            |b, _| {
+                b.iter(|| {
+                    black_box(
+                        engine
+                            .query_index_range(
+                                "users",
+                                "by_age",
+                                Bound::Included(&age),
+                                Bound::Unbounded,
+                                Some(20),
+                            )
+                            .expect("range"),
+                    )
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_batch_commit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("batch_commit");
+    group.sample_size(20);
+    let ops: Vec<WriteOperation> = (0..10).map(put_user).collect();
+    for size in TABLE_SIZES {
+        let engine = seeded_engine(size, 1);
+        group.bench_with_input(BenchmarkId::new("ten_puts", size), &size, |b, _| {
+            b.iter_batched(
+                || engine.snapshot(),
+                |mut engine| black_box(engine.write_batch("users", &ops).expect("write")),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_validation(c: &mut Criterion) {
+    let mut item_fields = BTreeMap::new();
+    item_fields.insert(
+        "sku".to_string(),
+        SchemaField {
+            required: true,
+            field_type: SchemaType::String,
+        },
+    );
+    item_fields.insert(
+        "qty".to_string(),
+        SchemaField {
+            required: true,
+            field_type: SchemaType::Number,
+        },
+    );
+    let mut fields = BTreeMap::new();
+    fields.insert(
+        "items".to_string(),
+        SchemaField {
+            required: true,
+            field_type: SchemaType::ArrayOf(Box::new(SchemaType::ObjectOf(item_fields))),
+        },
+    );
+    let schema = Schema::with_fields(fields);
+
+    let items: Vec<Value> = (0..10_000)
+        .map(|n| serde_json::json!({"sku": format!("sku-{n}"), "qty": n}))
+        .collect();
+    let mut document = BTreeMap::new();
+    document.insert("items".to_string(), Value::Array(items));
+
+    c.bench_function("validate_10k_nested_items", |b| {
+        b.iter(|| schema.validate(black_box(&document)).expect("valid"))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_inserts,
+    bench_reads,
+    bench_batch_commit,
+    bench_validation
+);
+criterion_main!(benches);
@@ -1,6 +1,7 @@
 use core_db::{InMemoryEngine, NewDocument, Schema, SchemaField, SchemaType, WriteOperation};
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use std::collections::BTreeMap;
+use std::ops::Bound;
 
 fn users_schema() -> Schema {
     let mut fields = BTreeMap::new();
@@ -42,5 +43,162 @@ fn bench_write_batch(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_write_batch);
+fn put_user(id: usize) -> WriteOperation {
+    let mut fields = BTreeMap::new();
+    fields.insert(
+        "name".to_string(),
+        serde_json::Value::String(format!("user {id}")),
+    );
+    fields.insert(
+        "tags".to_string(),
+        serde_json::Value::Array((0..32).map(serde_json::Value::from).collect()),
+    );
+    WriteOperation::Put(NewDocument {
+        id: Some(format!("u_{id}")),
+        fields,
+    })
+}
+
+fn seeded_engine(documents: usize) -> InMemoryEngine {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table creation should work");
+    let ops: Vec<WriteOperation> = (0..documents).map(put_user).collect();
+    engine
+        .write_batch("users", &ops)
+        .expect("seed should succeed");
+    engine
+}
+
+fn bench_large_table(c: &mut Criterion) {
+    let mut engine = seeded_engine(10_000);
+    let single = [put_user(0)];
+    c.bench_function("commit_single_put_into_10k_table", |b| {
+        b.iter(|| black_box(engine.write_batch("users", &single).expect("write")))
+    });
+
+    let replaces: Vec<WriteOperation> = (0..100).map(put_user).collect();
+    c.bench_function("replace_100_in_10k_table", |b| {
+        b.iter(|| black_box(engine.write_batch("users", &replaces).expect("write")))
+    });
+
+    c.bench_function("snapshot_10k_table", |b| {
+        b.iter(|| black_box(engine.snapshot()))
+    });
+}
+
+fn bench_indexed_inserts(c: &mut Criterion) {
+    let ops: Vec<WriteOperation> = (0..100_000).map(put_user).collect();
+    let mut group = c.benchmark_group("indexed_inserts");
+    group.sample_size(10);
+    group.bench_function("insert_100k_with_4_indexes", |b| {
+        b.iter(|| {
+            let mut engine = InMemoryEngine::new();
+            engine
+                .create_table("users", users_schema())
+                .expect("table creation should work");
+            for (name, field) in [
+                ("by_name", "name"),
+                ("by_tags", "tags"),
+                ("by_missing", "missing"),
+                ("by_name_again", "name"),
+            ] {
+                engine
+                    .create_index("users", name, &[field])
+                    .expect("index creation should work");
+            }
+            black_box(engine.write_batch("users", &ops).expect("write"))
+        })
+    });
+    group.finish();
+}
+
+fn bench_index_range(c: &mut Criterion) {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("events", Schema::default())
+        .expect("table creation should work");
+    engine
+        .create_index("events", "by_bucket", &["bucket"])
+        .expect("index creation should work");
+    let ops: Vec<WriteOperation> = (0..200_000)
+        .map(|id| {
+            let mut fields = BTreeMap::new();
+            fields.insert("bucket".to_string(), serde_json::Value::from(id % 1_000));
+            WriteOperation::Put(NewDocument {
+                id: Some(format!("e_{id}")),
+                fields,
+            })
+        })
+        .collect();
+    engine
+        .write_batch("events", &ops)
+        .expect("seed should succeed");
+
+    let lower = [serde_json::Value::from(100)];
+    c.bench_function("range_first_10_of_200k", |b| {
+        b.iter(|| {
+            black_box(
+                engine
+                    .query_index_range(
+                        "events",
+                        "by_bucket",
+                        Bound::Included(&lower),
+                        Bound::Unbounded,
+                        Some(10),
+                    )
+                    .expect("range"),
+            )
+        })
+    });
+}
+
+fn bench_index_backfill(c: &mut Criterion) {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("events", Schema::default())
+        .expect("table creation should work");
+    let ops: Vec<WriteOperation> = (0..500_000)
+        .map(|id| {
+            let mut fields = BTreeMap::new();
+            fields.insert("bucket".to_string(), serde_json::Value::from(id % 1_000));
+            fields.insert(
+                "payload".to_string(),
+                serde_json::Value::String("x".repeat(64)),
+            );
+            WriteOperation::Put(NewDocument {
+                id: Some(format!("e_{id}")),
+                fields,
+            })
+        })
+        .collect();
+    engine
+        .write_batch("events", &ops)
+        .expect("seed should succeed");
+
+    let mut group = c.benchmark_group("index_backfill");
+    group.sample_size(10);
+    group.bench_function("create_index_on_500k", |b| {
+        b.iter_batched(
+            || engine.snapshot(),
+            |mut copy| {
+                copy.create_index("events", "by_bucket", &["bucket"])
+                    .expect("index creation should work");
+                copy
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_index_backfill,
+    bench_write_batch,
+    bench_large_table,
+    bench_indexed_inserts,
+    bench_index_range
+);
 criterion_main!(benches);
@@ -0,0 +1,182 @@
+use crate::engine::InMemoryEngine;
+use crate::error::{CoreError, CoreResult};
+use crate::schema::Schema;
+use crate::types::{Document, DocumentId, TableName, WriteOperation};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot};
+
+type Reply<T> = oneshot::Sender<CoreResult<T>>;
+
+#[derive(Debug)]
+pub enum Command {
+    CreateTable {
+        table: TableName,
+        schema: Schema,
+        reply: Reply<()>,
+    },
+    Get {
+        table: TableName,
+        id: DocumentId,
+        reply: Reply<Document>,
+    },
+    ListDocuments {
+        table: TableName,
+        reply: Reply<Vec<Document>>,
+    },
+    WriteBatch {
+        table: TableName,
+        ops: Vec<WriteOperation>,
+        reply: Reply<Vec<Document>>,
+    },
+    RunExpiration {
+        now_ms: f64,
+        reply: Reply<usize>,
+    },
+}
+
+#[derive(Debug)]
+pub struct EngineActor {
+    engine: InMemoryEngine,
+    receiver: mpsc::UnboundedReceiver<Command>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EngineHandle {
+    sender: mpsc::UnboundedSender<Command>,
+}
+
+impl EngineActor {
+    pub fn new(engine: InMemoryEngine) -> (Self, EngineHandle) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { engine, receiver }, EngineHandle { sender })
+    }
+
+    pub fn spawn(engine: InMemoryEngine) -> EngineHandle {
+        let (actor, handle) = Self::new(engine);
+        thread::spawn(move || actor.run());
+        handle
+    }
+
+    /// Like `spawn`, and also sweeps expired documents every `interval`. Each
+    /// sweep is one `run_expiration` call, so it deletes at most the engine's
+    /// expiration batch limit. The ticker stops once every handle is dropped.
+    pub fn spawn_with_expiration(engine: InMemoryEngine, interval: Duration) -> EngineHandle {
+        let handle = Self::spawn(engine);
+        let ticker = handle.sender.downgrade();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let Some(sender) = ticker.upgrade() else {
+                break;
+            };
+            // Nobody waits on a scheduled sweep, so its result is dropped.
+            let (reply, _) = oneshot::channel();
+            let sweep = Command::RunExpiration {
+                now_ms: now_ms(),
+                reply,
+            };
+            if sender.send(sweep).is_err() {
+                break;
+            }
+        });
+        handle
+    }
+
+    pub fn run(mut self) -> InMemoryEngine {
+        while let Some(command) = self.receiver.blocking_recv() {
+            self.handle(command);
+        }
+        self.engine
+    }
+
+    fn handle(&mut self, command: Command) {
+        // A dropped reply receiver only means the caller stopped waiting.
+        match command {
+            Command::CreateTable {
+                table,
+                schema,
+                reply,
+            } => {
+                let _ = reply.send(self.engine.create_table(&table, schema));
+            }
+            Command::Get { table, id, reply } => {
+                let _ = reply.send(self.engine.get(&table, &id));
+            }
+            Command::ListDocuments { table, reply } => {
+                let _ = reply.send(self.engine.list_documents(&table));
+            }
+            Command::WriteBatch { table, ops, reply } => {
+                let _ = reply.send(self.engine.write_batch(&table, &ops));
+            }
+            Command::RunExpiration { now_ms, reply } => {
+                let _ = reply.send(self.engine.run_expiration(now_ms));
+            }
+        }
+    }
+}
+
+impl EngineHandle {
+    pub async fn create_table(&self, table: &str, schema: Schema) -> CoreResult<()> {
+        self.request(|reply| Command::CreateTable {
+            table: table.to_owned(),
+            schema,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn get(&self, table: &str, id: &str) -> CoreResult<Document> {
+        self.request(|reply| Command::Get {
+            table: table.to_owned(),
+            id: id.to_owned(),
+            reply,
+        })
+        .await
+    }
+
+    pub async fn list_documents(&self, table: &str) -> CoreResult<Vec<Document>> {
+        self.request(|reply| Command::ListDocuments {
+            table: table.to_owned(),
+            reply,
+        })
+        .await
+    }
+
+    pub async fn write_batch(
+        &self,
+        table: &str,
+        ops: Vec<WriteOperation>,
+    ) -> CoreResult<Vec<Document>> {
+        self.request(|reply| Command::WriteBatch {
+            table: table.to_owned(),
+            ops,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn run_expiration(&self, now_ms: f64) -> CoreResult<usize> {
+        self.request(|reply| Command::RunExpiration { now_ms, reply })
+            .await
+    }
+
+    async fn request<T>(&self, command: impl FnOnce(Reply<T>) -> Command) -> CoreResult<T> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(command(reply))
+            .map_err(|_| actor_stopped())?;
+        response.await.map_err(|_| actor_stopped())?
+    }
+}
+
+fn now_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+        * 1000.0
+}
+
+fn actor_stopped() -> CoreError {
+    CoreError::InvalidOperation("engine actor has stopped".to_string())
+}
@@ -1,21 +1,239 @@
 use crate::error::{CoreError, CoreResult};
-use crate::schema::Schema;
+use crate::ids::{IdGenerator, UuidV7Ids};
+use crate::index::{compare_values, Index, IndexDefinition, IndexKey, RangeOptions};
+use crate::metrics::{Metrics, NoopMetrics, OpKind, Outcome};
+use crate::migration::MIGRATIONS_TABLE;
+use crate::schema::{Schema, WireDatabaseSchema};
+use crate::stats::{estimated_document_size, EngineStats, OperationCounts, TableStats};
+use crate::trace::OpSpan;
 use crate::types::{
-    Document, DocumentId, NewDocument, Revision, TableName, TableState, WriteOperation,
+    Backup, CopyTableOptions, Document, DocumentId, HealthState, NewDocument, Quota, Revision,
+    TableDiff, TableName, TableSnapshot, TableState, TtlPolicy, Value, WriteOperation, AUDIT_TABLE,
+    ID_FIELD, REVISION_FIELD,
 };
-use std::collections::HashMap;
-use uuid::Uuid;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::ops::Bound;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_EXPIRATION_BATCH_LIMIT: usize = 1_000;
 
 #[derive(Debug, Clone)]
 struct Table {
-    schema: Schema,
-    documents: HashMap<DocumentId, Document>,
+    // Shared so the per-batch working copy doesn't deep-clone nested types.
+    schema: Arc<Schema>,
+    documents: BTreeMap<Arc<str>, Arc<Document>>,
+    estimated_bytes: usize,
+    quota: Option<Quota>,
+    ttl: Option<TtlPolicy>,
+    indexes: BTreeMap<String, Index>,
+}
+
+impl Table {
+    fn new(schema: Schema) -> Self {
+        Self {
+            schema: Arc::new(schema),
+            documents: BTreeMap::new(),
+            estimated_bytes: 0,
+            quota: None,
+            ttl: None,
+            indexes: BTreeMap::new(),
+        }
+    }
+
+    fn from_snapshot(snapshot: TableSnapshot) -> CoreResult<(TableName, Self, u64)> {
+        let mut table = Self::new(Schema::from_wire_table(&snapshot.name, &snapshot.schema)?);
+        table.quota = snapshot.quota;
+        table.ttl = snapshot.ttl;
+        for definition in snapshot.indexes {
+            table.add_index(definition)?;
+        }
+
+        let mut max_revision = 0;
+        for document in snapshot.documents {
+            table.schema.validate(&document.fields)?;
+            if table.documents.contains_key(document.id.as_str()) {
+                return Err(CoreError::InvalidOperation(format!(
+                    "snapshot contains duplicate document id: {}",
+                    document.id
+                )));
+            }
+            max_revision = max_revision.max(document.revision.0);
+            table.store(document);
+        }
+
+        Ok((snapshot.name, table, max_revision))
+    }
+
+    fn add_index(&mut self, definition: IndexDefinition) -> CoreResult<()> {
+        if self.indexes.contains_key(&definition.name) {
+            return Err(CoreError::IndexAlreadyExists(definition.name));
+        }
+
+        let mut index = Index::new(definition);
+        index.backfill(
+            self.documents
+                .iter()
+                .map(|(id, document)| (id, &document.fields)),
+        );
+        self.indexes.insert(index.definition().name.clone(), index);
+        Ok(())
+    }
+
+    fn rebuild_index(&mut self, name: &str) -> CoreResult<()> {
+        let index = self
+            .indexes
+            .get_mut(name)
+            .ok_or_else(|| CoreError::IndexNotFound(name.to_owned()))?;
+        index.clear();
+        index.backfill(
+            self.documents
+                .iter()
+                .map(|(id, document)| (id, &document.fields)),
+        );
+        Ok(())
+    }
+
+    fn stale_indexes(&self) -> Vec<String> {
+        self.indexes
+            .values()
+            .filter(|index| {
+                let mut fresh = index.empty_copy();
+                fresh.backfill(
+                    self.documents
+                        .iter()
+                        .map(|(id, document)| (id, &document.fields)),
+                );
+                fresh != **index
+            })
+            .map(|index| index.definition().name.clone())
+            .collect()
+    }
+
+    fn index_definitions(&self) -> Vec<IndexDefinition> {
+        self.indexes
+            .values()
+            .map(|index| index.definition().clone())
+            .collect()
+    }
+
+    fn store(&mut self, document: Document) {
+        self.estimated_bytes += estimated_document_size(&document);
+        let id = match self.documents.get_key_value(document.id.as_str()) {
+            Some((id, previous)) => {
+                self.estimated_bytes -= estimated_document_size(previous);
+                for index in self.indexes.values_mut() {
+                    index.update(id, &previous.fields, &document.fields);
+                }
+                Arc::clone(id)
+            }
+            None => {
+                let id: Arc<str> = Arc::from(document.id.as_str());
+                for index in self.indexes.values_mut() {
+                    index.insert(&id, &document.fields);
+                }
+                id
+            }
+        };
+        self.documents.insert(id, Arc::new(document));
+    }
+
+    fn remove(&mut self, table: &str, id: &str) -> CoreResult<Arc<Document>> {
+        let (id, removed) = self
+            .documents
+            .remove_entry(id)
+            .ok_or_else(|| CoreError::document_not_found(table, id))?;
+        self.estimated_bytes -= estimated_document_size(&removed);
+        for index in self.indexes.values_mut() {
+            index.remove(&id, &removed.fields);
+        }
+        Ok(removed)
+    }
+
+    /// True when every record has the `_audit:<revision>` id the audit log
+    /// gives the records it writes.
+    fn is_audit_log(&self) -> bool {
+        self.documents.values().all(|record| {
+            record
+                .id
+                .strip_prefix(AUDIT_TABLE)
+                .and_then(|rest| rest.strip_prefix(':'))
+                .is_some_and(|revision| revision == record.revision.0.to_string())
+        })
+    }
+
+    fn check_revision(&self, id: &str, expected: Revision) -> CoreResult<()> {
+        let actual = self.documents.get(id).map(|document| document.revision);
+        if actual == Some(expected) {
+            return Ok(());
+        }
+
+        Err(CoreError::RevisionMismatch {
+            id: id.to_owned(),
+            expected,
+            actual,
+        })
+    }
+
+    fn stats(&self, name: &str) -> TableStats {
+        TableStats {
+            name: name.to_owned(),
+            document_count: self.documents.len(),
+            estimated_bytes: self.estimated_bytes,
+            has_schema: !self.schema.fields.is_empty(),
+            quota: self.quota,
+            index_count: self.indexes.len(),
+            indexes: self.indexes.values().map(Index::stats).collect(),
+        }
+    }
+
+    fn check_quota(&self, name: &str, before: &Table) -> CoreResult<()> {
+        let Some(quota) = self.quota else {
+            return Ok(());
+        };
+
+        if let Some(max_documents) = quota.max_documents {
+            let count = self.documents.len();
+            if count > max_documents && count > before.documents.len() {
+                return Err(CoreError::QuotaExceeded(format!(
+                    "table {} would hold {} documents, limit is {}",
+                    name, count, max_documents
+                )));
+            }
+        }
+
+        if let Some(max_bytes) = quota.max_bytes {
+            if self.estimated_bytes > max_bytes && self.estimated_bytes > before.estimated_bytes {
+                return Err(CoreError::QuotaExceeded(format!(
+                    "table {} would hold {} bytes, limit is {}",
+                    name, self.estimated_bytes, max_bytes
+                )));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
 pub struct InMemoryEngine {
     tables: HashMap<TableName, Table>,
     next_revision: u64,
+    reads: AtomicU64,
+    operations: OperationCounts,
+    auto_create_tables: bool,
+    global_id_uniqueness: bool,
+    default_strict: bool,
+    default_max_fields: Option<usize>,
+    audit: bool,
+    actor: Option<String>,
+    expiration_batch_limit: usize,
+    metrics: Arc<dyn Metrics>,
+    id_generator: Arc<dyn IdGenerator>,
+    health: HealthState,
 }
 
 impl Default for InMemoryEngine {
@@ -23,6 +241,18 @@ impl Default for InMemoryEngine {
         Self {
             tables: HashMap::new(),
             next_revision: 1,
+            reads: AtomicU64::new(0),
+            operations: OperationCounts::default(),
+            auto_create_tables: false,
+            global_id_uniqueness: false,
+            default_strict: false,
+            default_max_fields: None,
+            audit: false,
+            actor: None,
+            expiration_batch_limit: DEFAULT_EXPIRATION_BATCH_LIMIT,
+            metrics: Arc::new(NoopMetrics),
+            id_generator: Arc::new(UuidV7Ids),
+            health: HealthState::Healthy,
         }
     }
 }
@@ -32,20 +262,270 @@ impl InMemoryEngine {
         Self::default()
     }
 
-    pub fn create_table(&mut self, table: &str, schema: Schema) -> CoreResult<()> {
-        if self.tables.contains_key(table) {
-            return Err(CoreError::TableAlreadyExists(table.to_owned()));
+    pub fn from_schema(schema: &WireDatabaseSchema) -> CoreResult<Self> {
+        let mut engine = Self::new();
+        for (table, collection) in schema {
+            engine.create_table(table, Schema::from_wire_table(table, collection)?)?;
         }
+        Ok(engine)
+    }
 
-        self.tables.insert(
-            table.to_owned(),
-            Table {
-                schema,
-                documents: HashMap::new(),
-            },
-        );
+    pub fn health(&self) -> &HealthState {
+        &self.health
+    }
 
-        Ok(())
+    /// Set by a persistence layer when durable state falls behind memory.
+    /// Operators call `clear_health` once the underlying problem is fixed.
+    pub fn set_health(&mut self, health: HealthState) {
+        self.health = health;
+    }
+
+    pub fn clear_health(&mut self) {
+        self.health = HealthState::Healthy;
+    }
+
+    pub fn set_metrics(&mut self, metrics: Arc<dyn Metrics>) {
+        self.metrics = metrics;
+    }
+
+    pub fn set_id_generator(&mut self, id_generator: Arc<dyn IdGenerator>) {
+        self.id_generator = id_generator;
+    }
+
+    pub fn set_auto_create_tables(&mut self, enabled: bool) {
+        self.auto_create_tables = enabled;
+    }
+
+    /// When enabled, a put that would create a document fails if any other
+    /// table already holds a document with the same id.
+    pub fn set_global_id_uniqueness(&mut self, enabled: bool) {
+        self.global_id_uniqueness = enabled;
+    }
+
+    /// When enabled, tables without declared fields reject user fields that
+    /// start with `_` and, if set, documents over `set_default_max_fields`.
+    pub fn set_default_strict(&mut self, enabled: bool) {
+        self.default_strict = enabled;
+    }
+
+    /// Caps the number of fields per document in schemaless tables. Only
+    /// enforced while default strictness is on.
+    pub fn set_default_max_fields(&mut self, limit: Option<usize>) {
+        self.default_max_fields = limit;
+    }
+
+    /// When enabled, every successful batch appends one record per changed
+    /// document to `AUDIT_TABLE`, which is created on first use. Records
+    /// hold `timestamp_ms`, `table`, `document`, `operation` (insert, update
+    /// or delete), the `before` and `after` fields where they exist, and the
+    /// `actor` set by `with_actor`. Their revision is the audit version and
+    /// their id is `_audit:<revision>`. Only the engine writes the table:
+    /// creating, importing, copying into, writing to or dropping it is
+    /// rejected, `prune_audit` is the way to trim it, and enabling the log
+    /// fails if a restored `_audit` table holds records it did not write. TTL expiry goes through `write_batch`, so expired
+    /// documents are audited as deletes.
+    pub fn set_audit_log(&mut self, enabled: bool) -> CoreResult<()> {
+        self.observe_write(OpKind::SetAuditLog, AUDIT_TABLE, |engine| {
+            engine.ensure_writable()?;
+            match engine.tables.get(AUDIT_TABLE) {
+                Some(existing) if enabled && !existing.is_audit_log() => {
+                    return Err(CoreError::InvalidOperation(format!(
+                        "{} holds records the audit log did not write",
+                        AUDIT_TABLE
+                    )));
+                }
+                Some(_) => {}
+                None if enabled => {
+                    engine
+                        .tables
+                        .insert(AUDIT_TABLE.to_owned(), Table::new(Schema::default()));
+                }
+                None => {}
+            }
+            engine.audit = enabled;
+            Ok(())
+        })
+    }
+
+    /// Runs `run` with `actor` recorded on any audit records it produces.
+    pub fn with_actor<T>(&mut self, actor: &str, run: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = self.actor.replace(actor.to_owned());
+        let result = run(self);
+        self.actor = previous;
+        result
+    }
+
+    /// Deletes audit records whose revision is below `before_version` and
+    /// returns how many were removed.
+    pub fn prune_audit(&mut self, before_version: u64) -> CoreResult<usize> {
+        self.observe_write(OpKind::PruneAudit, AUDIT_TABLE, |engine| {
+            engine.ensure_writable()?;
+            let audit = engine
+                .tables
+                .get_mut(AUDIT_TABLE)
+                .ok_or_else(|| CoreError::table_not_found(AUDIT_TABLE))?;
+            let stale: Vec<DocumentId> = audit
+                .documents
+                .values()
+                .filter(|record| record.revision.0 < before_version)
+                .map(|record| record.id.clone())
+                .collect();
+            for id in &stale {
+                audit.remove(AUDIT_TABLE, id)?;
+            }
+            Ok(stale.len())
+        })
+    }
+
+    pub fn create_table(&mut self, table: &str, schema: Schema) -> CoreResult<()> {
+        let started = Instant::now();
+        let span = OpSpan::enter(OpKind::CreateTable, table);
+        let result = if let Err(error) = self.ensure_writable() {
+            Err(error)
+        } else if let Err(error) = ensure_user_table(table) {
+            Err(error)
+        } else if self.tables.contains_key(table) {
+            Err(CoreError::TableAlreadyExists(table.to_owned()))
+        } else {
+            self.tables.insert(table.to_owned(), Table::new(schema));
+            Ok(())
+        };
+
+        self.finish_op(span, OpKind::CreateTable, table, started, &result);
+        result
+    }
+
+    pub fn contains_table(&self, table: &str) -> bool {
+        self.tables.contains_key(table)
+    }
+
+    pub fn drop_table(&mut self, table: &str) -> CoreResult<()> {
+        let started = Instant::now();
+        let span = OpSpan::enter(OpKind::DropTable, table);
+        let result = self.ensure_writable().and_then(|()| {
+            ensure_user_table(table)?;
+            self.tables
+                .remove(table)
+                .map(|_| ())
+                .ok_or_else(|| CoreError::table_not_found(table))
+        });
+
+        self.finish_op(span, OpKind::DropTable, table, started, &result);
+        result
+    }
+
+    /// Swaps in a new schema after checking every existing document against
+    /// it; the first document that fails is named in the error.
+    pub fn update_schema(&mut self, table: &str, schema: Schema) -> CoreResult<()> {
+        self.observe_write(OpKind::UpdateSchema, table, |engine| {
+            engine.ensure_writable()?;
+            let table_data = engine
+                .tables
+                .get_mut(table)
+                .ok_or_else(|| CoreError::table_not_found(table))?;
+
+            for document in table_data.documents.values() {
+                schema
+                    .validate(&document.fields)
+                    .map_err(|error| match error {
+                        CoreError::SchemaViolation { field, message, .. } => {
+                            CoreError::SchemaViolation {
+                                document: Some(document.id.to_string()),
+                                field,
+                                message,
+                            }
+                        }
+                        other => other,
+                    })?;
+            }
+            table_data.schema = Arc::new(schema);
+            Ok(())
+        })
+    }
+
+    /// Swaps in a new schema without checking existing documents. Only later
+    /// writes are validated against it.
+    pub fn update_schema_unchecked(&mut self, table: &str, schema: Schema) -> CoreResult<()> {
+        self.observe_write(OpKind::UpdateSchema, table, |engine| {
+            engine.ensure_writable()?;
+            engine
+                .tables
+                .get_mut(table)
+                .ok_or_else(|| CoreError::table_not_found(table))?
+                .schema = Arc::new(schema);
+            Ok(())
+        })
+    }
+
+    pub fn set_table_quota(&mut self, table: &str, quota: Option<Quota>) -> CoreResult<()> {
+        self.observe_write(OpKind::SetQuota, table, |engine| {
+            engine.ensure_writable()?;
+            let table_data = engine
+                .tables
+                .get_mut(table)
+                .ok_or_else(|| CoreError::table_not_found(table))?;
+            table_data.quota = quota;
+            Ok(())
+        })
+    }
+
+    pub fn set_ttl(&mut self, table: &str, policy: Option<TtlPolicy>) -> CoreResult<()> {
+        self.observe_write(OpKind::SetTtl, table, |engine| {
+            engine.ensure_writable()?;
+            ensure_user_table(table)?;
+            let table_data = engine
+                .tables
+                .get_mut(table)
+                .ok_or_else(|| CoreError::table_not_found(table))?;
+            table_data.ttl = policy;
+            Ok(())
+        })
+    }
+
+    pub fn set_expiration_batch_limit(&mut self, limit: usize) {
+        self.expiration_batch_limit = limit;
+    }
+
+    /// Deletes at most the configured batch limit of expired documents per call,
+    /// so callers sweeping a large backlog should call this repeatedly.
+    pub fn run_expiration(&mut self, now_ms: f64) -> CoreResult<usize> {
+        let mut names: Vec<TableName> = self
+            .tables
+            .iter()
+            .filter(|(_, table)| table.ttl.is_some())
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+
+        let mut remaining = self.expiration_batch_limit;
+        let mut deleted = 0;
+        for name in names {
+            if remaining == 0 {
+                break;
+            }
+
+            let expired: Vec<WriteOperation> = match self.tables.get(&name) {
+                Some(Table {
+                    ttl: Some(policy),
+                    documents,
+                    ..
+                }) => documents
+                    .values()
+                    .filter(|document| policy.is_expired(document, now_ms))
+                    .take(remaining)
+                    .map(|document| WriteOperation::Delete(document.id.clone()))
+                    .collect(),
+                _ => continue,
+            };
+
+            if !expired.is_empty() {
+                self.write_batch(&name, &expired)?;
+                remaining -= expired.len();
+                deleted += expired.len();
+            }
+        }
+
+        Ok(deleted)
     }
 
     pub fn list_tables(&self) -> Vec<TableState> {
@@ -61,28 +541,741 @@ impl InMemoryEngine {
         states
     }
 
-    pub fn get(&self, table: &str, id: &str) -> CoreResult<Document> {
-        let table_data = self
+    pub fn stats(&self) -> EngineStats {
+        let mut tables: Vec<TableStats> = self
             .tables
+            .iter()
+            .map(|(name, table)| table.stats(name))
+            .collect();
+        tables.sort_by(|left, right| left.name.cmp(&right.name));
+
+        let mut operations = self.operations.clone();
+        operations.reads = self.reads.load(Ordering::Relaxed);
+
+        EngineStats {
+            total_documents: tables.iter().map(|table| table.document_count).sum(),
+            total_estimated_bytes: tables.iter().map(|table| table.estimated_bytes).sum(),
+            tables,
+            current_revision: self.next_revision - 1,
+            operations,
+        }
+    }
+
+    pub fn table_stats(&self, table: &str) -> CoreResult<TableStats> {
+        self.tables
             .get(table)
-            .ok_or_else(|| CoreError::TableNotFound(table.to_owned()))?;
+            .map(|table_data| table_data.stats(table))
+            .ok_or_else(|| CoreError::table_not_found(table))
+    }
+
+    /// An independent copy of the data. The copy mints ids from its own fork
+    /// of the id generator and reports to no metrics sink until `set_metrics`.
+    pub fn snapshot(&self) -> Self {
+        let mut snapshot = self.working_copy();
+        snapshot.id_generator = self.id_generator.fork();
+        snapshot.metrics = Arc::new(NoopMetrics);
+        snapshot
+    }
+
+    /// Like `snapshot`, but shares the id generator and metrics sink, for
+    /// copies whose tables are moved back with `adopt_tables` on success.
+    pub(crate) fn working_copy(&self) -> Self {
+        Self {
+            tables: self.tables.clone(),
+            next_revision: self.next_revision,
+            reads: AtomicU64::new(self.reads.load(Ordering::Relaxed)),
+            operations: self.operations.clone(),
+            auto_create_tables: self.auto_create_tables,
+            global_id_uniqueness: self.global_id_uniqueness,
+            default_strict: self.default_strict,
+            default_max_fields: self.default_max_fields,
+            audit: self.audit,
+            actor: self.actor.clone(),
+            expiration_batch_limit: self.expiration_batch_limit,
+            metrics: Arc::clone(&self.metrics),
+            id_generator: Arc::clone(&self.id_generator),
+            health: self.health.clone(),
+        }
+    }
+
+    /// Takes the data from a `working_copy`: its tables, revision counter,
+    /// audit state and operation counts. Settings stay as they are on `self`.
+    pub(crate) fn adopt_tables(&mut self, working: Self) {
+        self.tables = working.tables;
+        self.next_revision = working.next_revision;
+        self.audit = working.audit;
+        self.operations = working.operations;
+    }
 
-        table_data
+    /// Creates an engine-maintained table such as `MIGRATIONS_TABLE` if it is
+    /// missing. User calls to `create_table` reject these names.
+    pub(crate) fn create_system_table(&mut self, table: &str) -> CoreResult<()> {
+        self.ensure_writable()?;
+        self.tables
+            .entry(table.to_owned())
+            .or_insert_with(|| Table::new(Schema::default()));
+        Ok(())
+    }
+
+    pub fn diff(&self, other: &Self) -> Vec<TableDiff> {
+        let names: BTreeSet<&TableName> = self.tables.keys().chain(other.tables.keys()).collect();
+        let empty = BTreeMap::new();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let before = self
+                    .tables
+                    .get(name)
+                    .map_or(&empty, |table| &table.documents);
+                let after = other
+                    .tables
+                    .get(name)
+                    .map_or(&empty, |table| &table.documents);
+
+                let mut diff = TableDiff {
+                    name: name.clone(),
+                    ..TableDiff::default()
+                };
+                for (id, document) in after {
+                    match before.get(id) {
+                        None => diff.added.push(document.id.clone()),
+                        Some(previous) if previous.fields != document.fields => {
+                            diff.changed.push(document.id.clone())
+                        }
+                        Some(_) => {}
+                    }
+                }
+                diff.removed = before
+                    .keys()
+                    .filter(|id| !after.contains_key(*id))
+                    .map(|id| id.to_string())
+                    .collect();
+
+                diff.added.sort();
+                diff.removed.sort();
+                diff.changed.sort();
+                diff
+            })
+            .filter(|diff| !diff.is_empty())
+            .collect()
+    }
+
+    pub fn copy_table(
+        &mut self,
+        source: &str,
+        destination: &str,
+        options: CopyTableOptions,
+    ) -> CoreResult<usize> {
+        self.observe_write(OpKind::CopyTable, destination, |engine| {
+            engine.copy_documents(source, destination, options)
+        })
+    }
+
+    fn copy_documents(
+        &mut self,
+        source: &str,
+        destination: &str,
+        options: CopyTableOptions,
+    ) -> CoreResult<usize> {
+        ensure_user_table(destination)?;
+        let source_table = self
+            .tables
+            .get(source)
+            .ok_or_else(|| CoreError::table_not_found(source))?;
+        let schema = if options.copy_schema {
+            Schema::clone(&source_table.schema)
+        } else {
+            Schema::default()
+        };
+        let index_definitions = source_table.index_definitions();
+        let ops: Vec<WriteOperation> = source_table
             .documents
-            .get(id)
-            .cloned()
-            .ok_or_else(|| CoreError::DocumentNotFound(id.to_owned()))
+            .values()
+            .map(|document| {
+                WriteOperation::Put(NewDocument {
+                    id: options.preserve_ids.then(|| document.id.clone()),
+                    fields: document.fields.clone(),
+                })
+            })
+            .collect();
+
+        let created = match self.tables.get(destination) {
+            Some(existing) if !existing.documents.is_empty() && !options.merge => {
+                return Err(CoreError::InvalidOperation(format!(
+                    "cannot copy into non-empty table {} without merge",
+                    destination
+                )));
+            }
+            Some(_) => false,
+            None => {
+                self.create_table(destination, schema)?;
+                for definition in index_definitions {
+                    self.tables
+                        .get_mut(destination)
+                        .ok_or_else(|| CoreError::table_not_found(destination))?
+                        .add_index(definition)?;
+                }
+                true
+            }
+        };
+
+        match self.write_batch(destination, &ops) {
+            Ok(written) => Ok(written.len()),
+            Err(error) => {
+                if created {
+                    self.tables.remove(destination);
+                }
+                Err(error)
+            }
+        }
     }
 
-    pub fn list_documents(&self, table: &str) -> CoreResult<Vec<Document>> {
+    pub fn export_table(&self, table: &str) -> CoreResult<TableSnapshot> {
+        let documents = self.list_documents(table)?;
         let table_data = self
             .tables
             .get(table)
-            .ok_or_else(|| CoreError::TableNotFound(table.to_owned()))?;
+            .ok_or_else(|| CoreError::table_not_found(table))?;
+
+        Ok(TableSnapshot {
+            name: table.to_owned(),
+            schema: table_data.schema.to_wire(),
+            quota: table_data.quota,
+            ttl: table_data.ttl.clone(),
+            indexes: table_data.index_definitions(),
+            documents,
+        })
+    }
+
+    pub fn export_table_ndjson(&self, table: &str, mut writer: impl Write) -> CoreResult<usize> {
+        self.observe_read(OpKind::Scan, table, |table_data| {
+            let mut written = 0;
+            for document in table_data.documents.values() {
+                serde_json::to_writer(&mut writer, &document.to_value())
+                    .map_err(|error| CoreError::Io(error.to_string()))?;
+                writer
+                    .write_all(b"\n")
+                    .map_err(|error| CoreError::Io(error.to_string()))?;
+                written += 1;
+            }
+            writer
+                .flush()
+                .map_err(|error| CoreError::Io(error.to_string()))?;
+
+            Ok(written)
+        })
+    }
+
+    /// Imports one JSON object per line as a single atomic batch. An `_id` field
+    /// is reused as the document id; `_revision` is dropped because revisions are
+    /// assigned by this engine.
+    pub fn import_ndjson(&mut self, table: &str, reader: impl BufRead) -> CoreResult<usize> {
+        let mut ops = Vec::new();
+        let mut line_numbers = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.map_err(|error| CoreError::Io(error.to_string()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let mut fields = match serde_json::from_str::<Value>(&line) {
+                Ok(Value::Object(map)) => map,
+                Ok(_) => {
+                    return Err(CoreError::InvalidOperation(format!(
+                        "line {}: expected a JSON object",
+                        line_number
+                    )))
+                }
+                Err(error) => {
+                    return Err(CoreError::InvalidOperation(format!(
+                        "line {}: {}",
+                        line_number, error
+                    )))
+                }
+            };
+
+            fields.remove(REVISION_FIELD);
+            let id = match fields.remove(ID_FIELD) {
+                None => None,
+                Some(Value::String(id)) if !id.is_empty() => Some(id),
+                Some(other) => {
+                    return Err(CoreError::InvalidOperation(format!(
+                        "line {}: invalid {} {}",
+                        line_number, ID_FIELD, other
+                    )))
+                }
+            };
+
+            ops.push(WriteOperation::Put(NewDocument {
+                id,
+                fields: fields.into_iter().collect(),
+            }));
+            line_numbers.push(line_number);
+        }
+
+        self.write_batch(table, &ops)
+            .map(|written| written.len())
+            .map_err(|error| match error {
+                CoreError::WithContext {
+                    op,
+                    table,
+                    id,
+                    item,
+                    source,
+                    ..
+                } => CoreError::WithContext {
+                    op,
+                    table,
+                    id,
+                    item,
+                    line: line_numbers.get(item).copied(),
+                    source,
+                },
+                other => other,
+            })
+    }
+
+    pub fn import_table(&mut self, snapshot: TableSnapshot) -> CoreResult<()> {
+        let name = snapshot.name.clone();
+        self.observe_write(OpKind::Import, &name, |engine| {
+            engine.ensure_writable()?;
+            ensure_user_table(&snapshot.name)?;
+            if engine.tables.contains_key(&snapshot.name) {
+                return Err(CoreError::TableAlreadyExists(snapshot.name));
+            }
+
+            let (name, table, max_revision) = Table::from_snapshot(snapshot)?;
+            engine.next_revision = engine.next_revision.max(max_revision + 1);
+            engine.tables.insert(name, table);
+            Ok(())
+        })
+    }
+
+    pub fn backup(&self) -> CoreResult<Backup> {
+        let mut names: Vec<&TableName> = self.tables.keys().collect();
+        names.sort();
+
+        let tables = names
+            .into_iter()
+            .map(|name| self.export_table(name))
+            .collect::<CoreResult<Vec<_>>>()?;
+
+        Ok(Backup {
+            revision: self.next_revision - 1,
+            tables,
+        })
+    }
+
+    /// Replaces every table with the backup's contents. Nothing changes if any
+    /// table fails to rebuild. Revisions are never reused: the counter continues
+    /// from whichever is higher, the live engine or the backup. The live audit
+    /// log is kept, gaining any records from the backup it doesn't already
+    /// have. Metrics record it under an empty table name.
+    pub fn restore(&mut self, backup: Backup) -> CoreResult<()> {
+        self.observe_write(OpKind::Restore, "", |engine| {
+            engine.ensure_writable()?;
+            let mut tables = HashMap::new();
+            let mut max_revision = backup.revision;
+            for snapshot in backup.tables {
+                let (name, table, table_revision) = Table::from_snapshot(snapshot)?;
+                if tables.insert(name.clone(), table).is_some() {
+                    return Err(CoreError::TableAlreadyExists(name));
+                }
+                max_revision = max_revision.max(table_revision);
+            }
+
+            if let Some(mut audit) = engine.tables.remove(AUDIT_TABLE) {
+                if let Some(restored) = tables.remove(AUDIT_TABLE) {
+                    for (id, record) in restored.documents {
+                        if !audit.documents.contains_key(&id) {
+                            audit.store(Document::clone(&record));
+                        }
+                    }
+                }
+                tables.insert(AUDIT_TABLE.to_owned(), audit);
+            }
+
+            engine.tables = tables;
+            engine.next_revision = engine.next_revision.max(max_revision + 1);
+            Ok(())
+        })
+    }
 
-        let mut docs: Vec<Document> = table_data.documents.values().cloned().collect();
-        docs.sort_by(|left, right| left.id.cmp(&right.id));
-        Ok(docs)
+    pub fn create_index(&mut self, table: &str, name: &str, fields: &[&str]) -> CoreResult<()> {
+        self.create_index_with(table, IndexDefinition::new(name, fields))
+    }
+
+    pub fn create_sorted_index(
+        &mut self,
+        table: &str,
+        name: &str,
+        fields: &[&str],
+        sort_by: &str,
+    ) -> CoreResult<()> {
+        let mut definition = IndexDefinition::new(name, fields);
+        definition.sort_by = Some(sort_by.to_owned());
+        self.create_index_with(table, definition)
+    }
+
+    /// Array and object fields can only be indexed when the definition sets
+    /// `multikey`, which files each array element under its own key. Only
+    /// fields the table's schema declares are checked.
+    pub fn create_index_with(
+        &mut self,
+        table: &str,
+        definition: IndexDefinition,
+    ) -> CoreResult<()> {
+        let started = Instant::now();
+        let span = OpSpan::enter(OpKind::CreateIndex, table);
+        span.record_index(&definition.name);
+        let result = self.add_index(table, definition);
+        self.finish_op(span, OpKind::CreateIndex, table, started, &result);
+        result
+    }
+
+    fn add_index(&mut self, table: &str, definition: IndexDefinition) -> CoreResult<()> {
+        self.ensure_writable()?;
+        if definition.fields.is_empty() {
+            return Err(CoreError::invalid_index(
+                &definition.name,
+                "must cover at least one field",
+            ));
+        }
+
+        let table_data = self
+            .tables
+            .get_mut(table)
+            .ok_or_else(|| CoreError::table_not_found(table))?;
+        if !definition.multikey {
+            for field in &definition.fields {
+                match table_data.schema.fields.get(field) {
+                    Some(declared) if !declared.field_type.is_scalar() => {
+                        return Err(CoreError::invalid_index(
+                            &definition.name,
+                            format!(
+                                "covers non-scalar field {} ({}); set multikey to index its elements",
+                                field,
+                                declared.field_type.as_str()
+                            ),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        table_data.add_index(definition)
+    }
+
+    /// Clears the named index and re-inserts every document, keeping the
+    /// definition (fields and sort order) as it was.
+    pub fn rebuild_index(&mut self, table: &str, index: &str) -> CoreResult<()> {
+        let started = Instant::now();
+        let span = OpSpan::enter(OpKind::RebuildIndex, table);
+        span.record_index(index);
+        let result = self.ensure_writable().and_then(|()| {
+            self.tables
+                .get_mut(table)
+                .ok_or_else(|| CoreError::table_not_found(table))?
+                .rebuild_index(index)
+        });
+        self.finish_op(span, OpKind::RebuildIndex, table, started, &result);
+        result
+    }
+
+    /// Checks every index on the table against one rebuilt from its documents.
+    pub fn verify_indexes(&self, table: &str) -> CoreResult<()> {
+        let stale = self
+            .tables
+            .get(table)
+            .ok_or_else(|| CoreError::table_not_found(table))?
+            .stale_indexes();
+        if stale.is_empty() {
+            Ok(())
+        } else {
+            Err(CoreError::InvalidOperation(format!(
+                "indexes out of date on {}: {}",
+                table,
+                stale.join(", ")
+            )))
+        }
+    }
+
+    /// Names a single-field index on `field`, or `None` when a scan is needed.
+    /// There are no unique indexes, so among several candidates a plain
+    /// (non-multikey) index wins, then the first by name.
+    pub fn best_index_for(&self, table: &str, field: &str) -> CoreResult<Option<&str>> {
+        let table_data = self
+            .tables
+            .get(table)
+            .ok_or_else(|| CoreError::table_not_found(table))?;
+
+        Ok(table_data
+            .indexes
+            .values()
+            .map(Index::definition)
+            .filter(|definition| definition.fields == [field])
+            .min_by_key(|definition| definition.multikey)
+            .map(|definition| definition.name.as_str()))
+    }
+
+    pub fn list_indexes(&self, table: &str) -> CoreResult<Vec<IndexDefinition>> {
+        self.tables
+            .get(table)
+            .map(Table::index_definitions)
+            .ok_or_else(|| CoreError::table_not_found(table))
+    }
+
+    /// Returns documents whose indexed fields equal `values`, ordered by the
+    /// index's `sort_by` field when it has one and then by id.
+    /// Key equality follows `IndexKey`, so `1` and `1.0` match each other.
+    pub fn query_index(
+        &self,
+        table: &str,
+        index: &str,
+        values: &[Value],
+    ) -> CoreResult<Vec<Document>> {
+        self.observe_index_read(table, index, |table_data, index_data| {
+            Ok(index_data
+                .lookup(values)?
+                .filter_map(|id| table_data.documents.get(id))
+                .map(|document| Document::clone(document))
+                .collect())
+        })
+    }
+
+    /// Unions `query_index` over several keys. Keys are deduplicated and
+    /// visited in index order, and each document appears once even when a
+    /// multikey index files it under more than one of the keys.
+    pub fn query_index_in(
+        &self,
+        table: &str,
+        index: &str,
+        keys: &[&[Value]],
+    ) -> CoreResult<Vec<Document>> {
+        self.observe_index_read(table, index, |table_data, index_data| {
+            let keys: BTreeSet<IndexKey> = keys.iter().map(|key| IndexKey(key.to_vec())).collect();
+
+            let mut seen = HashSet::new();
+            let mut documents = Vec::new();
+            for key in &keys {
+                documents.extend(
+                    index_data
+                        .lookup(&key.0)?
+                        .filter(|id| seen.insert(*id))
+                        .filter_map(|id| table_data.documents.get(id))
+                        .map(|document| Document::clone(document)),
+                );
+            }
+            Ok(documents)
+        })
+    }
+
+    /// Like `query_index` but accepts fewer values than the index has fields;
+    /// the missing trailing fields match anything.
+    pub fn query_index_partial(
+        &self,
+        table: &str,
+        index: &str,
+        prefix: &[Value],
+    ) -> CoreResult<Vec<Document>> {
+        self.observe_index_read(table, index, |table_data, index_data| {
+            Ok(index_data
+                .lookup_prefix(prefix)?
+                .filter_map(|id| table_data.documents.get(id))
+                .map(|document| Document::clone(document))
+                .collect())
+        })
+    }
+
+    /// Returns documents whose keys fall between the bounds, in index order.
+    /// With a limit, both the index walk and the document copies stop early.
+    pub fn query_index_range(
+        &self,
+        table: &str,
+        index: &str,
+        lower: Bound<&[Value]>,
+        upper: Bound<&[Value]>,
+        limit: Option<usize>,
+    ) -> CoreResult<Vec<Document>> {
+        self.query_index_range_with(
+            table,
+            index,
+            lower,
+            upper,
+            RangeOptions {
+                limit,
+                ..RangeOptions::default()
+            },
+        )
+    }
+
+    /// Like `query_index_range`, with `skip_null` to leave out documents whose
+    /// first indexed field is null or missing, e.g. so `age < 35` does not
+    /// return documents without an age.
+    pub fn query_index_range_with(
+        &self,
+        table: &str,
+        index: &str,
+        lower: Bound<&[Value]>,
+        upper: Bound<&[Value]>,
+        options: RangeOptions,
+    ) -> CoreResult<Vec<Document>> {
+        self.observe_index_read(table, index, |table_data, index_data| {
+            Ok(index_data
+                .range_iter(lower, upper, options.skip_null)?
+                .filter_map(|id| table_data.documents.get(id))
+                .take(options.limit.unwrap_or(usize::MAX))
+                .map(|document| Document::clone(document))
+                .collect())
+        })
+    }
+
+    pub fn get(&self, table: &str, id: &str) -> CoreResult<Document> {
+        self.observe_read(OpKind::Get, table, |table_data| {
+            table_data
+                .documents
+                .get(id)
+                .map(|document| Document::clone(document))
+                .ok_or_else(|| CoreError::document_not_found(table, id))
+        })
+    }
+
+    pub fn get_opt(&self, table: &str, id: &str) -> CoreResult<Option<Document>> {
+        self.observe_read(OpKind::Get, table, |table_data| {
+            Ok(table_data
+                .documents
+                .get(id)
+                .map(|document| Document::clone(document)))
+        })
+    }
+
+    pub fn contains(&self, table: &str, id: &str) -> CoreResult<bool> {
+        self.observe_read(OpKind::Get, table, |table_data| {
+            Ok(table_data.documents.contains_key(id))
+        })
+    }
+
+    pub fn list_documents(&self, table: &str) -> CoreResult<Vec<Document>> {
+        self.list_documents_page(table, None, usize::MAX)
+    }
+
+    /// Returns up to `limit` documents ordered by id, starting strictly after
+    /// `after_id` when a cursor is given.
+    pub fn list_documents_page(
+        &self,
+        table: &str,
+        after_id: Option<&str>,
+        limit: usize,
+    ) -> CoreResult<Vec<Document>> {
+        self.observe_read(OpKind::List, table, |table_data| {
+            let range = match after_id {
+                Some(after_id) => table_data
+                    .documents
+                    .range::<str, _>((Bound::Excluded(after_id), Bound::Unbounded)),
+                None => table_data.documents.range::<str, _>(..),
+            };
+            Ok(range
+                .map(|(_, document)| Document::clone(document))
+                .take(limit)
+                .collect())
+        })
+    }
+
+    /// Borrows the table's documents in id order without copying them.
+    pub fn iter_entries(
+        &self,
+        table: &str,
+    ) -> CoreResult<impl Iterator<Item = (&DocumentId, &Document)> + '_> {
+        self.observe_read(OpKind::Scan, table, |table_data| {
+            Ok(table_data
+                .documents
+                .values()
+                .map(|document| (&document.id, document.as_ref())))
+        })
+    }
+
+    pub fn entries(&self, table: &str) -> CoreResult<Vec<(&DocumentId, &Document)>> {
+        Ok(self.iter_entries(table)?.collect())
+    }
+
+    /// Whether any document matches, stopping at the first that does.
+    pub fn any(&self, table: &str, predicate: impl FnMut(&Document) -> bool) -> CoreResult<bool> {
+        Ok(self.find_first(table, predicate)?.is_some())
+    }
+
+    /// The first document in id order that matches, without scanning past it.
+    pub fn find_first(
+        &self,
+        table: &str,
+        mut predicate: impl FnMut(&Document) -> bool,
+    ) -> CoreResult<Option<&Document>> {
+        self.observe_read(OpKind::Scan, table, |table_data| {
+            Ok(table_data
+                .documents
+                .values()
+                .map(|document| document.as_ref())
+                .find(|document| predicate(document)))
+        })
+    }
+
+    pub fn count_documents(&self, table: &str) -> CoreResult<usize> {
+        self.observe_read(OpKind::Count, table, |table_data| {
+            Ok(table_data.documents.len())
+        })
+    }
+
+    pub fn list_documents_limited(
+        &self,
+        table: &str,
+        offset: usize,
+        limit: usize,
+    ) -> CoreResult<Vec<Document>> {
+        self.observe_read(OpKind::List, table, |table_data| {
+            Ok(table_data
+                .documents
+                .values()
+                .skip(offset)
+                .take(limit)
+                .map(|document| Document::clone(document))
+                .collect())
+        })
+    }
+
+    /// Scans the whole table. `query_index` on a multikey index over `field`
+    /// answers the same question without a scan.
+    pub fn find_array_contains(
+        &self,
+        table: &str,
+        field: &str,
+        value: &Value,
+    ) -> CoreResult<Vec<Document>> {
+        self.observe_read(OpKind::Scan, table, |table_data| {
+            Ok(table_data
+                .documents
+                .values()
+                .filter(|document| match document.fields.get(field) {
+                    Some(Value::Array(items)) => items.contains(value),
+                    _ => false,
+                })
+                .map(|document| Document::clone(document))
+                .collect())
+        })
+    }
+
+    pub fn count_where(
+        &self,
+        table: &str,
+        predicate: impl Fn(&Document) -> bool,
+    ) -> CoreResult<usize> {
+        self.observe_read(OpKind::Count, table, |table_data| {
+            Ok(table_data
+                .documents
+                .values()
+                .filter(|document| predicate(document))
+                .count())
+        })
     }
 
     pub fn write_batch(
@@ -90,44 +1283,480 @@ impl InMemoryEngine {
         table: &str,
         ops: &[WriteOperation],
     ) -> CoreResult<Vec<Document>> {
-        let existing = self
-            .tables
-            .get(table)
-            .ok_or_else(|| CoreError::TableNotFound(table.to_owned()))?;
+        self.run_batch(table, ops, false)
+    }
 
-        let schema = existing.schema.clone();
-        let mut documents = existing.documents.clone();
-        let mut written_docs = Vec::new();
+    /// `write_batch` for tables the engine maintains itself.
+    pub(crate) fn write_system_batch(
+        &mut self,
+        table: &str,
+        ops: &[WriteOperation],
+    ) -> CoreResult<Vec<Document>> {
+        self.run_batch(table, ops, true)
+    }
 
-        for op in ops {
-            match op {
-                WriteOperation::Put(input) => {
-                    schema.validate(&input.fields)?;
-                    let id = resolve_document_id(input);
-                    let document = Document {
-                        id: id.clone(),
-                        revision: self.next_revision(),
-                        fields: input.fields.clone(),
-                    };
-                    documents.insert(id, document.clone());
-                    written_docs.push(document);
-                }
-                WriteOperation::Delete(id) => {
-                    let deleted = documents.remove(id);
-                    if deleted.is_none() {
-                        return Err(CoreError::DocumentNotFound(id.clone()));
+    fn run_batch(
+        &mut self,
+        table: &str,
+        ops: &[WriteOperation],
+        system: bool,
+    ) -> CoreResult<Vec<Document>> {
+        let started = Instant::now();
+        let span = OpSpan::enter(OpKind::WriteBatch, table);
+        span.record_documents(ops.len());
+        self.operations.batches += 1;
+        let result = self.apply_batch(table, ops, system);
+        self.finish_op(span, OpKind::WriteBatch, table, started, &result);
+
+        match &result {
+            Ok(_) => {
+                for op in ops {
+                    match op {
+                        WriteOperation::Put(_) | WriteOperation::PutIf { .. } => {
+                            self.operations.puts += 1
+                        }
+                        WriteOperation::Patch { .. }
+                        | WriteOperation::Increment { .. }
+                        | WriteOperation::ArrayPush { .. }
+                        | WriteOperation::ArrayRemove { .. } => self.operations.patches += 1,
+                        WriteOperation::Delete(_) | WriteOperation::DeleteIf { .. } => {
+                            self.operations.deletes += 1
+                        }
                     }
                 }
             }
+            Err(_) => self.operations.failed_batches += 1,
         }
 
-        if let Some(table_data) = self.tables.get_mut(table) {
-            table_data.documents = documents;
+        result
+    }
+
+    fn apply_batch(
+        &mut self,
+        table: &str,
+        ops: &[WriteOperation],
+        system: bool,
+    ) -> CoreResult<Vec<Document>> {
+        self.ensure_writable()?;
+        if !system {
+            ensure_user_table(table)?;
         }
+        let mut working = match self.tables.get(table) {
+            Some(existing) => existing.clone(),
+            None if self.auto_create_tables => Table::new(Schema::default()),
+            None => return Err(CoreError::table_not_found(table)),
+        };
+        let mut written_docs = Vec::new();
+
+        for (item, op) in ops.iter().enumerate() {
+            let written = self
+                .apply_op(table, &mut working, op)
+                .map_err(|source| CoreError::in_batch(table, item, op, source))?;
+            written_docs.extend(written);
+        }
+
+        if let Some(before) = self.tables.get(table) {
+            working.check_quota(table, before)?;
+        }
+        if self.audit {
+            self.append_audit(table, ops, &written_docs, &working);
+        }
+        self.tables.insert(table.to_owned(), working);
 
         Ok(written_docs)
     }
 
+    /// Records one audit entry per document the batch changed, comparing the
+    /// committed table with the working copy about to replace it. Audit
+    /// records skip schema validation and are never audited themselves.
+    fn append_audit(
+        &mut self,
+        table: &str,
+        ops: &[WriteOperation],
+        written: &[Document],
+        after: &Table,
+    ) {
+        let touched: BTreeSet<&str> = written
+            .iter()
+            .map(|document| document.id.as_str())
+            .chain(ops.iter().filter_map(WriteOperation::document_id))
+            .collect();
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1000.0;
+
+        let mut records = Vec::new();
+        for id in touched {
+            let before = self
+                .tables
+                .get(table)
+                .and_then(|committed| committed.documents.get(id));
+            let after = after.documents.get(id);
+            let operation = match (before, after) {
+                (None, Some(_)) => "insert",
+                (Some(_), Some(_)) => "update",
+                (Some(_), None) => "delete",
+                (None, None) => continue,
+            };
+
+            let mut fields = BTreeMap::new();
+            fields.insert("timestamp_ms".to_string(), Value::from(timestamp_ms));
+            fields.insert("table".to_string(), Value::from(table));
+            fields.insert("document".to_string(), Value::from(id));
+            fields.insert("operation".to_string(), Value::from(operation));
+            for (key, image) in [("before", before), ("after", after)] {
+                if let Some(document) = image {
+                    fields.insert(key.to_string(), document.user_fields_json());
+                }
+            }
+            if let Some(actor) = &self.actor {
+                fields.insert("actor".to_string(), Value::from(actor.as_str()));
+            }
+            records.push(fields);
+        }
+
+        for fields in records {
+            let revision = self.next_revision();
+            let document = Document {
+                id: format!("{}:{}", AUDIT_TABLE, revision.0),
+                revision,
+                fields,
+            };
+            self.tables
+                .entry(AUDIT_TABLE.to_owned())
+                .or_insert_with(|| Table::new(Schema::default()))
+                .store(document);
+        }
+    }
+
+    fn apply_op(
+        &mut self,
+        table: &str,
+        working: &mut Table,
+        op: &WriteOperation,
+    ) -> CoreResult<Option<Document>> {
+        match op {
+            WriteOperation::Put(input) => self.put_document(table, working, input).map(Some),
+            WriteOperation::PutIf { document, expected } => {
+                let id = document.id.as_deref().ok_or_else(|| {
+                    CoreError::InvalidOperation("PutIf requires an explicit id".to_string())
+                })?;
+                working.check_revision(id, *expected)?;
+                self.put_document(table, working, document).map(Some)
+            }
+            WriteOperation::Patch { id, fields } => {
+                let existing = working
+                    .documents
+                    .get(id.as_str())
+                    .ok_or_else(|| CoreError::document_not_found(table, id))?;
+                let mut merged = existing.fields.clone();
+                merged.extend(
+                    fields
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.clone())),
+                );
+                self.validate_fields(&working.schema, &merged)?;
+
+                let document = Document {
+                    id: id.clone(),
+                    revision: self.next_revision(),
+                    fields: merged,
+                };
+                working.store(document.clone());
+                Ok(Some(document))
+            }
+            WriteOperation::Increment { id, field, delta } => self
+                .update_field(table, working, id, field, |current| {
+                    let current = match current {
+                        None => 0,
+                        Some(value) => value.as_i64().ok_or_else(|| {
+                            CoreError::InvalidOperation(format!(
+                                "cannot increment field {} of document {}: {} is not an integer",
+                                field, id, value
+                            ))
+                        })?,
+                    };
+                    current.checked_add(*delta).map(Value::from).ok_or_else(|| {
+                        CoreError::InvalidOperation(format!(
+                            "incrementing field {} of document {} overflows",
+                            field, id
+                        ))
+                    })
+                })
+                .map(Some),
+            WriteOperation::ArrayPush { id, field, value } => self
+                .update_field(table, working, id, field, |current| {
+                    let mut elements = array_field(current, field, id)?;
+                    if !elements.iter().any(|element| same_value(element, value)) {
+                        elements.push(value.clone());
+                    }
+                    Ok(Value::Array(elements))
+                })
+                .map(Some),
+            WriteOperation::ArrayRemove { id, field, value } => self
+                .update_field(table, working, id, field, |current| {
+                    let mut elements = array_field(current, field, id)?;
+                    elements.retain(|element| !same_value(element, value));
+                    Ok(Value::Array(elements))
+                })
+                .map(Some),
+            WriteOperation::Delete(id) => working.remove(table, id).map(|_| None),
+            WriteOperation::DeleteIf { id, expected } => {
+                working.check_revision(id, *expected)?;
+                working.remove(table, id).map(|_| None)
+            }
+        }
+    }
+
+    /// Adds `delta` to an integer field in one write and returns the new
+    /// value. An absent field starts at zero; any other type is an error.
+    pub fn increment(&mut self, table: &str, id: &str, field: &str, delta: i64) -> CoreResult<i64> {
+        let written = self.write_single(
+            table,
+            WriteOperation::Increment {
+                id: id.to_owned(),
+                field: field.to_owned(),
+                delta,
+            },
+        )?;
+        written
+            .fields
+            .get(field)
+            .and_then(Value::as_i64)
+            .ok_or_else(|| {
+                CoreError::Internal(format!("increment of {} did not write an integer", field))
+            })
+    }
+
+    /// Appends `value` to an array field unless an equal element is already
+    /// present, creating the array when the field is absent.
+    pub fn array_push(
+        &mut self,
+        table: &str,
+        id: &str,
+        field: &str,
+        value: Value,
+    ) -> CoreResult<Document> {
+        self.write_single(
+            table,
+            WriteOperation::ArrayPush {
+                id: id.to_owned(),
+                field: field.to_owned(),
+                value,
+            },
+        )
+    }
+
+    /// Removes every element equal to `value` from an array field.
+    pub fn array_remove(
+        &mut self,
+        table: &str,
+        id: &str,
+        field: &str,
+        value: Value,
+    ) -> CoreResult<Document> {
+        self.write_single(
+            table,
+            WriteOperation::ArrayRemove {
+                id: id.to_owned(),
+                field: field.to_owned(),
+                value,
+            },
+        )
+    }
+
+    fn write_single(&mut self, table: &str, op: WriteOperation) -> CoreResult<Document> {
+        let mut written = self.write_batch(table, &[op])?;
+        written.pop().ok_or_else(|| {
+            CoreError::Internal("single write did not produce a document".to_string())
+        })
+    }
+
+    /// Runs the checks a put would (writability, table lookup, schema)
+    /// without storing anything or generating an id. Quotas are not checked.
+    pub fn validate_insert(&self, table: &str, fields: &BTreeMap<String, Value>) -> CoreResult<()> {
+        self.ensure_writable()?;
+        match self.tables.get(table) {
+            Some(table_data) => self.validate_fields(&table_data.schema, fields),
+            None if self.auto_create_tables => self.validate_fields(&Schema::default(), fields),
+            None => Err(CoreError::table_not_found(table)),
+        }
+    }
+
+    pub fn insert_typed<T: Serialize>(&mut self, table: &str, value: &T) -> CoreResult<Document> {
+        let input = NewDocument::from_serializable(None, value)?;
+        self.write_single(table, WriteOperation::Put(input))
+    }
+
+    fn put_document(
+        &mut self,
+        table: &str,
+        working: &mut Table,
+        input: &NewDocument,
+    ) -> CoreResult<Document> {
+        self.validate_fields(&working.schema, &input.fields)?;
+        let id = match &input.id {
+            Some(explicit) => explicit.clone(),
+            None => self.id_generator.generate(table),
+        };
+        if self.global_id_uniqueness && !working.documents.contains_key(id.as_str()) {
+            if let Some((owner, _)) = self.tables.iter().find(|(name, other)| {
+                name.as_str() != table && other.documents.contains_key(id.as_str())
+            }) {
+                return Err(CoreError::InvalidOperation(format!(
+                    "document id {} already exists in table {}",
+                    id, owner
+                )));
+            }
+        }
+
+        let document = Document {
+            id,
+            revision: self.next_revision(),
+            fields: input.fields.clone(),
+        };
+        working.store(document.clone());
+        Ok(document)
+    }
+
+    /// Rewrites one field of an existing document from its current value,
+    /// validating and storing the result like a patch would.
+    fn update_field(
+        &mut self,
+        table: &str,
+        working: &mut Table,
+        id: &str,
+        field: &str,
+        update: impl FnOnce(Option<&Value>) -> CoreResult<Value>,
+    ) -> CoreResult<Document> {
+        let existing = working
+            .documents
+            .get(id)
+            .ok_or_else(|| CoreError::document_not_found(table, id))?;
+        let mut fields = existing.fields.clone();
+        let value = update(fields.get(field))?;
+        fields.insert(field.to_owned(), value);
+        self.validate_fields(&working.schema, &fields)?;
+
+        let document = Document {
+            id: id.to_owned(),
+            revision: self.next_revision(),
+            fields,
+        };
+        working.store(document.clone());
+        Ok(document)
+    }
+
+    /// Checks fields against the table schema, then applies the default
+    /// strictness guards when the schema declares no fields.
+    fn validate_fields(&self, schema: &Schema, fields: &BTreeMap<String, Value>) -> CoreResult<()> {
+        schema.validate(fields)?;
+        if !self.default_strict || !schema.fields.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(reserved) = fields.keys().find(|key| key.starts_with('_')) {
+            return Err(CoreError::field_violation(
+                reserved,
+                format!(
+                    "field '{}' is reserved: names starting with '_' are system fields",
+                    reserved
+                ),
+            ));
+        }
+        match self.default_max_fields {
+            Some(limit) if fields.len() > limit => Err(CoreError::schema_violation(format!(
+                "document has {} fields, more than the limit of {}",
+                fields.len(),
+                limit
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    fn ensure_writable(&self) -> CoreResult<()> {
+        match &self.health {
+            HealthState::ReadOnly(reason) => Err(CoreError::ReadOnly(reason.clone())),
+            HealthState::Healthy | HealthState::Degraded(_) => Ok(()),
+        }
+    }
+
+    fn observe_read<'a, T>(
+        &'a self,
+        op: OpKind,
+        table: &str,
+        read: impl FnOnce(&'a Table) -> CoreResult<T>,
+    ) -> CoreResult<T> {
+        let started = Instant::now();
+        let span = OpSpan::enter(op, table);
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let result = self
+            .tables
+            .get(table)
+            .ok_or_else(|| CoreError::table_not_found(table))
+            .and_then(read);
+        self.finish_op(span, op, table, started, &result);
+        result
+    }
+
+    /// `observe_read` for a query against one index of the table.
+    fn observe_index_read(
+        &self,
+        table: &str,
+        index: &str,
+        read: impl FnOnce(&Table, &Index) -> CoreResult<Vec<Document>>,
+    ) -> CoreResult<Vec<Document>> {
+        let started = Instant::now();
+        let span = OpSpan::enter(OpKind::IndexQuery, table);
+        span.record_index(index);
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        let result = self
+            .tables
+            .get(table)
+            .ok_or_else(|| CoreError::table_not_found(table))
+            .and_then(|table_data| {
+                let index_data = table_data
+                    .indexes
+                    .get(index)
+                    .ok_or_else(|| CoreError::IndexNotFound(index.to_owned()))?;
+                read(table_data, index_data)
+            });
+        if let Ok(documents) = &result {
+            span.record_documents(documents.len());
+        }
+        self.finish_op(span, OpKind::IndexQuery, table, started, &result);
+        result
+    }
+
+    /// Meters and spans a mutation that doesn't go through `write_batch`.
+    fn observe_write<T>(
+        &mut self,
+        op: OpKind,
+        table: &str,
+        write: impl FnOnce(&mut Self) -> CoreResult<T>,
+    ) -> CoreResult<T> {
+        let started = Instant::now();
+        let span = OpSpan::enter(op, table);
+        let result = write(self);
+        self.finish_op(span, op, table, started, &result);
+        result
+    }
+
+    fn finish_op<T>(
+        &self,
+        span: OpSpan,
+        op: OpKind,
+        table: &str,
+        started: Instant,
+        result: &CoreResult<T>,
+    ) {
+        let elapsed = started.elapsed();
+        self.metrics.record(op, table, elapsed, Outcome::of(result));
+        span.finish(result, elapsed);
+    }
+
     fn next_revision(&mut self) -> Revision {
         let current = self.next_revision;
         self.next_revision += 1;
@@ -135,10 +1764,32 @@ impl InMemoryEngine {
     }
 }
 
-fn resolve_document_id(input: &NewDocument) -> DocumentId {
-    match &input.id {
-        Some(explicit) => explicit.clone(),
-        None => Uuid::now_v7().to_string(),
+/// Rejects user writes to tables the engine maintains itself.
+fn ensure_user_table(table: &str) -> CoreResult<()> {
+    let owner = match table {
+        AUDIT_TABLE => "the audit log; use prune_audit to trim it",
+        MIGRATIONS_TABLE => "run_migrations",
+        _ => return Ok(()),
+    };
+    Err(CoreError::InvalidOperation(format!(
+        "{} is maintained by {}",
+        table, owner
+    )))
+}
+
+/// Equality as indexes see it, so `1` and `1.0` are the same element.
+fn same_value(left: &Value, right: &Value) -> bool {
+    compare_values(left, right).is_eq()
+}
+
+fn array_field(current: Option<&Value>, field: &str, id: &str) -> CoreResult<Vec<Value>> {
+    match current {
+        None => Ok(Vec::new()),
+        Some(Value::Array(elements)) => Ok(elements.clone()),
+        Some(other) => Err(CoreError::InvalidOperation(format!(
+            "field {} of document {} is not an array: {}",
+            field, id, other
+        ))),
     }
 }
 
@@ -148,6 +1799,7 @@ mod tests {
     use crate::schema::{Schema, SchemaField, SchemaType};
     use crate::types::{NewDocument, WriteOperation};
     use std::collections::BTreeMap;
+    use std::sync::Arc;
 
     fn users_schema() -> Schema {
         let mut fields = BTreeMap::new();
@@ -161,69 +1813,81 @@ mod tests {
         Schema::with_fields(fields)
     }
 
+    fn put_user(id: &str, name: &str) -> WriteOperation {
+        let mut fields = BTreeMap::new();
+        fields.insert("name".to_string(), serde_json::json!(name));
+        WriteOperation::Put(NewDocument {
+            id: Some(id.to_string()),
+            fields,
+        })
+    }
+
     #[test]
-    fn writes_and_reads_documents() {
+    fn rebuild_index_repairs_drifted_entries() {
         let mut engine = InMemoryEngine::new();
         engine
             .create_table("users", users_schema())
             .expect("table should be created");
+        engine
+            .create_index("users", "by_name", &["name"])
+            .expect("index should be created");
+        engine
+            .write_batch("users", &[put_user("u_1", "Ada"), put_user("u_2", "Ada")])
+            .expect("seed should succeed");
+        assert!(engine.verify_indexes("users").is_ok());
 
-        let mut doc_fields = BTreeMap::new();
-        doc_fields.insert(
-            "name".to_string(),
-            serde_json::Value::String("Grace".to_string()),
-        );
+        let table = engine.tables.get_mut("users").expect("table exists");
+        let (id, drifted) = table
+            .documents
+            .get_key_value("u_1")
+            .map(|(id, document)| (Arc::clone(id), Arc::clone(document)))
+            .expect("document exists");
+        table
+            .indexes
+            .get_mut("by_name")
+            .expect("index exists")
+            .remove(&id, &drifted.fields);
 
-        let result = engine
-            .write_batch(
-                "users",
-                &[WriteOperation::Put(NewDocument {
-                    id: Some("user_1".to_string()),
-                    fields: doc_fields,
-                })],
-            )
-            .expect("write should succeed");
+        let ada = [serde_json::json!("Ada")];
+        assert_eq!(
+            engine
+                .query_index("users", "by_name", &ada)
+                .expect("query")
+                .len(),
+            1
+        );
+        assert!(engine.verify_indexes("users").is_err());
 
-        assert_eq!(result.len(), 1);
-        let fetched = engine.get("users", "user_1").expect("doc must exist");
-        assert_eq!(fetched.id, "user_1");
-        assert_eq!(fetched.revision.0, 1);
+        engine
+            .rebuild_index("users", "by_name")
+            .expect("rebuild should succeed");
+        assert!(engine.verify_indexes("users").is_ok());
+        assert_eq!(
+            engine
+                .query_index("users", "by_name", &ada)
+                .expect("query")
+                .len(),
+            2
+        );
+        assert!(engine.rebuild_index("users", "missing").is_err());
     }
 
     #[test]
-    fn batch_is_atomic_on_validation_failure() {
+    fn snapshots_share_schema_until_it_is_replaced() {
         let mut engine = InMemoryEngine::new();
         engine
             .create_table("users", users_schema())
             .expect("table should be created");
+        let before = engine.snapshot();
+        assert!(Arc::ptr_eq(
+            &engine.tables["users"].schema,
+            &before.tables["users"].schema
+        ));
 
-        let mut good_fields = BTreeMap::new();
-        good_fields.insert(
-            "name".to_string(),
-            serde_json::Value::String("Ada".to_string()),
-        );
-
-        let mut bad_fields = BTreeMap::new();
-        bad_fields.insert("name".to_string(), serde_json::Value::Bool(false));
-
-        let result = engine.write_batch(
-            "users",
-            &[
-                WriteOperation::Put(NewDocument {
-                    id: Some("user_1".to_string()),
-                    fields: good_fields,
-                }),
-                WriteOperation::Put(NewDocument {
-                    id: Some("user_2".to_string()),
-                    fields: bad_fields,
-                }),
-            ],
-        );
-
-        assert!(result.is_err());
-        let listed = engine
-            .list_documents("users")
-            .expect("listing should succeed");
-        assert!(listed.is_empty());
+        engine
+            .update_schema("users", Schema::default())
+            .expect("schema update should succeed");
+        assert!(engine.tables["users"].schema.fields.is_empty());
+        assert_eq!(before.tables["users"].schema.fields.len(), 1);
     }
 }
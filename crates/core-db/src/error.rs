@@ -1,3 +1,5 @@
+use crate::types::{Revision, WriteOperation};
+use serde::ser::{Serialize, SerializeMap, Serializer};
 use thiserror::Error;
 
 pub type CoreResult<T> = Result<T, CoreError>;
@@ -6,12 +8,327 @@ pub type CoreResult<T> = Result<T, CoreError>;
 pub enum CoreError {
     #[error("table already exists: {0}")]
     TableAlreadyExists(String),
-    #[error("table not found: {0}")]
-    TableNotFound(String),
-    #[error("document not found: {0}")]
-    DocumentNotFound(String),
+    #[error("table not found: {table}")]
+    TableNotFound { table: String },
+    #[error("document not found in {table}: {id}")]
+    DocumentNotFound { table: String, id: String },
+    #[error("index already exists: {0}")]
+    IndexAlreadyExists(String),
+    #[error("index not found: {0}")]
+    IndexNotFound(String),
+    #[error("invalid index {index}: {message}")]
+    InvalidIndex { index: String, message: String },
     #[error("invalid operation: {0}")]
     InvalidOperation(String),
-    #[error("schema violation: {0}")]
-    SchemaViolation(String),
+    /// `field` names the offending field path when the failure is tied to
+    /// one; `document` is set when an existing document failed validation.
+    #[error(
+        "schema violation: {}{message}",
+        document.as_ref().map_or_else(String::new, |id| format!("document {id}: "))
+    )]
+    SchemaViolation {
+        document: Option<String>,
+        field: Option<String>,
+        message: String,
+    },
+    #[error(
+        "revision mismatch for document {id}: expected {expected}, found {}",
+        actual.map_or_else(|| "none".to_string(), |revision| revision.to_string())
+    )]
+    RevisionMismatch {
+        id: String,
+        expected: Revision,
+        actual: Option<Revision>,
+    },
+    #[error("namespace already exists: {0}")]
+    NamespaceAlreadyExists(String),
+    #[error("namespace not found: {0}")]
+    NamespaceNotFound(String),
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("engine is read-only: {0}")]
+    ReadOnly(String),
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String),
+    /// An engine invariant did not hold. Returned instead of panicking.
+    #[error("internal error: {0}")]
+    Internal(String),
+    /// Wraps the failure of one item in a batch with which item it was.
+    /// `item` is the operation's position in the batch; imports also set
+    /// the 1-based input `line`.
+    #[error(
+        "{op} #{item}{} on {table}{}: {source}",
+        line.map_or_else(String::new, |line| format!(" (line {line})")),
+        id.as_ref().map_or_else(String::new, |id| format!(" for document {id}"))
+    )]
+    WithContext {
+        op: &'static str,
+        table: String,
+        id: Option<String>,
+        item: usize,
+        line: Option<usize>,
+        #[source]
+        source: Box<CoreError>,
+    },
+}
+
+impl CoreError {
+    pub(crate) fn table_not_found(table: impl Into<String>) -> Self {
+        Self::TableNotFound {
+            table: table.into(),
+        }
+    }
+
+    pub(crate) fn document_not_found(table: impl Into<String>, id: impl Into<String>) -> Self {
+        Self::DocumentNotFound {
+            table: table.into(),
+            id: id.into(),
+        }
+    }
+
+    pub(crate) fn in_batch(
+        table: &str,
+        item: usize,
+        op: &WriteOperation,
+        source: CoreError,
+    ) -> Self {
+        Self::WithContext {
+            op: op.kind(),
+            table: table.to_owned(),
+            id: op.document_id().map(str::to_owned),
+            item,
+            line: None,
+            source: Box::new(source),
+        }
+    }
+
+    /// The underlying error, with any batch context stripped.
+    pub fn root(&self) -> &CoreError {
+        match self {
+            Self::WithContext { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    pub(crate) fn schema_violation(message: impl Into<String>) -> Self {
+        Self::SchemaViolation {
+            document: None,
+            field: None,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn field_violation(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::SchemaViolation {
+            document: None,
+            field: Some(field.into()),
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn invalid_index(index: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::InvalidIndex {
+            index: index.into(),
+            message: message.into(),
+        }
+    }
+
+    /// The table this error is about, when it names one.
+    pub fn table(&self) -> Option<&str> {
+        match self {
+            Self::WithContext { table, .. } => Some(table),
+            Self::TableAlreadyExists(table)
+            | Self::TableNotFound { table }
+            | Self::DocumentNotFound { table, .. } => Some(table),
+            _ => None,
+        }
+    }
+
+    /// The document id this error is about, when it names one.
+    pub fn document_id(&self) -> Option<&str> {
+        match self {
+            Self::WithContext { id, source, .. } => id.as_deref().or_else(|| source.document_id()),
+            Self::DocumentNotFound { id, .. } | Self::RevisionMismatch { id, .. } => Some(id),
+            Self::SchemaViolation { document, .. } => document.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Stable, machine-readable code for this error. Codes never change once
+    /// published, so clients can branch on them instead of parsing messages.
+    /// Context wrappers report the code of the error they wrap.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Self::WithContext { source, .. } => source.error_code(),
+            Self::TableAlreadyExists(_) => "TABLE_ALREADY_EXISTS",
+            Self::TableNotFound { .. } => "TABLE_NOT_FOUND",
+            Self::DocumentNotFound { .. } => "DOCUMENT_NOT_FOUND",
+            Self::IndexAlreadyExists(_) => "INDEX_ALREADY_EXISTS",
+            Self::IndexNotFound(_) => "INDEX_NOT_FOUND",
+            Self::InvalidIndex { .. } => "INVALID_INDEX",
+            Self::InvalidOperation(_) => "INVALID_OPERATION",
+            Self::SchemaViolation { .. } => "SCHEMA_VIOLATION",
+            Self::RevisionMismatch { .. } => "REVISION_CONFLICT",
+            Self::NamespaceAlreadyExists(_) => "NAMESPACE_ALREADY_EXISTS",
+            Self::NamespaceNotFound(_) => "NAMESPACE_NOT_FOUND",
+            Self::Io(_) => "IO_ERROR",
+            Self::ReadOnly(_) => "READ_ONLY",
+            Self::QuotaExceeded(_) => "QUOTA_EXCEEDED",
+            Self::Internal(_) => "INTERNAL",
+        }
+    }
+
+    /// Whether repeating the same operation against fresh state can succeed.
+    /// Only revision conflicts qualify; everything else fails the same way
+    /// until the caller changes its input or the engine's configuration.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.root(), Self::RevisionMismatch { .. })
+    }
+}
+
+/// Errors serialize as `{"code", "message", ...}` plus the structured
+/// payload of the variant, so they can be returned to clients as-is.
+impl Serialize for CoreError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("code", self.error_code())?;
+        map.serialize_entry("message", &self.to_string())?;
+        match self {
+            Self::TableAlreadyExists(table) | Self::TableNotFound { table } => {
+                map.serialize_entry("table", table)?;
+            }
+            Self::DocumentNotFound { table, id } => {
+                map.serialize_entry("table", table)?;
+                map.serialize_entry("id", id)?;
+            }
+            Self::IndexAlreadyExists(index) | Self::IndexNotFound(index) => {
+                map.serialize_entry("index", index)?;
+            }
+            Self::InvalidIndex { index, .. } => map.serialize_entry("index", index)?,
+            Self::SchemaViolation {
+                document, field, ..
+            } => {
+                if let Some(document) = document {
+                    map.serialize_entry("document", document)?;
+                }
+                if let Some(field) = field {
+                    map.serialize_entry("field", field)?;
+                }
+            }
+            Self::RevisionMismatch {
+                id,
+                expected,
+                actual,
+            } => {
+                map.serialize_entry("id", id)?;
+                map.serialize_entry("expected", expected)?;
+                map.serialize_entry("actual", actual)?;
+            }
+            Self::NamespaceAlreadyExists(namespace) | Self::NamespaceNotFound(namespace) => {
+                map.serialize_entry("namespace", namespace)?;
+            }
+            Self::WithContext {
+                op,
+                table,
+                id,
+                item,
+                line,
+                source,
+            } => {
+                map.serialize_entry("op", op)?;
+                map.serialize_entry("table", table)?;
+                if let Some(id) = id {
+                    map.serialize_entry("id", id)?;
+                }
+                map.serialize_entry("item", item)?;
+                if let Some(line) = line {
+                    map.serialize_entry("line", line)?;
+                }
+                map.serialize_entry("source", source)?;
+            }
+            Self::InvalidOperation(_)
+            | Self::Io(_)
+            | Self::ReadOnly(_)
+            | Self::QuotaExceeded(_)
+            | Self::Internal(_) => {}
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_revision_conflicts_are_retryable() {
+        let conflict = CoreError::RevisionMismatch {
+            id: "u_1".to_string(),
+            expected: Revision(1),
+            actual: Some(Revision(2)),
+        };
+        assert!(conflict.is_retryable());
+        assert_eq!(conflict.error_code(), "REVISION_CONFLICT");
+        assert!(!CoreError::QuotaExceeded("full".to_string()).is_retryable());
+        assert!(!CoreError::field_violation("age", "bad").is_retryable());
+    }
+
+    #[test]
+    fn not_found_errors_expose_table_and_id() {
+        let error = CoreError::document_not_found("users", "u_1");
+        assert_eq!(error.table(), Some("users"));
+        assert_eq!(error.document_id(), Some("u_1"));
+        assert_eq!(error.to_string(), "document not found in users: u_1");
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({
+                "code": "DOCUMENT_NOT_FOUND",
+                "message": "document not found in users: u_1",
+                "table": "users",
+                "id": "u_1",
+            })
+        );
+
+        let error = CoreError::table_not_found("users");
+        assert_eq!(error.table(), Some("users"));
+        assert_eq!(error.document_id(), None);
+    }
+
+    #[test]
+    fn context_wraps_the_failing_batch_item() {
+        let op = WriteOperation::Delete("u_1".to_string());
+        let error = CoreError::in_batch(
+            "users",
+            3,
+            &op,
+            CoreError::document_not_found("users", "u_1"),
+        );
+        assert_eq!(
+            error.to_string(),
+            "delete #3 on users for document u_1: document not found in users: u_1"
+        );
+        assert_eq!(error.error_code(), "DOCUMENT_NOT_FOUND");
+        assert!(matches!(error.root(), CoreError::DocumentNotFound { .. }));
+        assert!(std::error::Error::source(&error).is_some());
+        assert_eq!(serde_json::to_value(&error).unwrap()["source"]["id"], "u_1");
+    }
+
+    #[test]
+    fn serializes_code_message_and_payload() {
+        let error = CoreError::SchemaViolation {
+            document: Some("u_1".to_string()),
+            field: Some("age".to_string()),
+            message: "missing required field: age".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({
+                "code": "SCHEMA_VIOLATION",
+                "message": "schema violation: document u_1: missing required field: age",
+                "document": "u_1",
+                "field": "age",
+            })
+        );
+    }
 }
@@ -0,0 +1,204 @@
+use crate::error::{CoreError, CoreResult};
+use crate::types::{DocumentId, Value};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex, PoisonError};
+use uuid::Uuid;
+
+/// Supplies ids for puts that don't carry one.
+pub trait IdGenerator: Debug + Send + Sync {
+    fn generate(&self, table: &str) -> DocumentId;
+
+    /// A generator that continues from this one's current state without
+    /// sharing it, used when an engine is snapshotted.
+    fn fork(&self) -> Arc<dyn IdGenerator>;
+}
+
+#[derive(Debug, Default)]
+pub struct UuidV7Ids;
+
+impl IdGenerator for UuidV7Ids {
+    fn generate(&self, _table: &str) -> DocumentId {
+        Uuid::now_v7().to_string()
+    }
+
+    fn fork(&self) -> Arc<dyn IdGenerator> {
+        Arc::new(Self)
+    }
+}
+
+/// Hands out `table:1`, `table:2`, ... with one counter per table. Counters
+/// are not rewound when a batch fails, so a rejected put still uses up its id.
+#[derive(Debug, Default)]
+pub struct SequentialIds {
+    counters: Mutex<HashMap<String, u64>>,
+}
+
+impl SequentialIds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn generate(&self, table: &str) -> DocumentId {
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        let counter = counters.entry(table.to_owned()).or_default();
+        *counter += 1;
+        format!("{}:{}", table, counter)
+    }
+
+    fn fork(&self) -> Arc<dyn IdGenerator> {
+        let counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        Arc::new(Self {
+            counters: Mutex::new(counters.clone()),
+        })
+    }
+}
+
+/// Names a table at the type level for `TypedId`.
+pub trait Table {
+    const NAME: &'static str;
+}
+
+/// A `DocumentId` tagged with the table it belongs to, so ids from different
+/// tables can't be mixed up at compile time. Serializes as the bare id.
+pub struct TypedId<T: Table> {
+    id: DocumentId,
+    table: PhantomData<fn() -> T>,
+}
+
+impl<T: Table> TypedId<T> {
+    pub fn new(id: impl Into<DocumentId>) -> Self {
+        Self {
+            id: id.into(),
+            table: PhantomData,
+        }
+    }
+
+    pub fn table(&self) -> &'static str {
+        T::NAME
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.id
+    }
+
+    pub fn into_inner(self) -> DocumentId {
+        self.id
+    }
+}
+
+impl<T: Table> Clone for TypedId<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.id.clone())
+    }
+}
+
+impl<T: Table> PartialEq for TypedId<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T: Table> Eq for TypedId<T> {}
+
+impl<T: Table> Hash for TypedId<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T: Table> Debug for TypedId<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "TypedId<{}>({:?})", T::NAME, self.id)
+    }
+}
+
+impl<T: Table> Display for TypedId<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.id)
+    }
+}
+
+impl<T: Table> From<TypedId<T>> for DocumentId {
+    fn from(id: TypedId<T>) -> Self {
+        id.id
+    }
+}
+
+impl<T: Table> From<TypedId<T>> for Value {
+    fn from(id: TypedId<T>) -> Self {
+        Value::String(id.id)
+    }
+}
+
+impl<T: Table> TryFrom<&Value> for TypedId<T> {
+    type Error = CoreError;
+
+    fn try_from(value: &Value) -> CoreResult<Self> {
+        value.as_str().map(Self::new).ok_or_else(|| {
+            CoreError::InvalidOperation(format!("{} id must be a string, got {}", T::NAME, value))
+        })
+    }
+}
+
+impl<T: Table> Serialize for TypedId<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.id.serialize(serializer)
+    }
+}
+
+impl<'de, T: Table> Deserialize<'de> for TypedId<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        DocumentId::deserialize(deserializer).map(Self::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Table, TypedId};
+    use crate::types::{DocumentId, Value};
+    use serde_json::json;
+
+    struct Users;
+    struct Posts;
+
+    impl Table for Users {
+        const NAME: &'static str = "users";
+    }
+
+    impl Table for Posts {
+        const NAME: &'static str = "posts";
+    }
+
+    fn author_of(_post: &TypedId<Posts>) -> TypedId<Users> {
+        TypedId::new("u_1")
+    }
+
+    #[test]
+    fn typed_ids_are_distinct_per_table() {
+        let post = TypedId::<Posts>::new("p_1");
+        let author: TypedId<Users> = author_of(&post);
+        assert_eq!(author.table(), "users");
+        assert_eq!(post.table(), "posts");
+        assert_eq!(format!("{author:?}"), "TypedId<users>(\"u_1\")");
+    }
+
+    #[test]
+    fn typed_ids_round_trip_through_strings_and_json() {
+        let id = TypedId::<Users>::new("u_7");
+        let value = Value::from(id.clone());
+        assert_eq!(value, json!("u_7"));
+        assert_eq!(TypedId::<Users>::try_from(&value).expect("string id"), id);
+        assert!(TypedId::<Users>::try_from(&json!(7)).is_err());
+
+        let encoded = serde_json::to_string(&id).expect("serialize");
+        assert_eq!(encoded, "\"u_7\"");
+        let decoded: TypedId<Users> = serde_json::from_str(&encoded).expect("deserialize");
+        assert_eq!(DocumentId::from(decoded), "u_7");
+    }
+}
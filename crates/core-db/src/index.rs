@@ -0,0 +1,626 @@
+use crate::error::{CoreError, CoreResult};
+use crate::stats::{estimated_value_size, IndexStats};
+use crate::types::Value;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ops::Bound;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct IndexDefinition {
+    pub name: String,
+    pub fields: Vec<String>,
+    /// Orders documents that share a key by this field, then by id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<String>,
+    /// Indexes each element of an array field as its own key, so a lookup by
+    /// one element finds every document whose array contains it. Objects and
+    /// empty arrays are keyed whole.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub multikey: bool,
+}
+
+impl IndexDefinition {
+    pub fn new(name: &str, fields: &[&str]) -> Self {
+        Self {
+            name: name.to_owned(),
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            sort_by: None,
+            multikey: false,
+        }
+    }
+}
+
+/// Options for range queries over an index.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RangeOptions {
+    /// Stops after this many documents.
+    pub limit: Option<usize>,
+    /// Leaves out entries whose first key component is null, which includes
+    /// documents missing the field.
+    pub skip_null: bool,
+}
+
+/// A comparable wrapper over the JSON values of an index key.
+///
+/// Values order by type first (null < boolean < number < string < array <
+/// object) and then by value. Numbers compare numerically, so `1` and `1.0`
+/// are the same key. Arrays compare element-wise and objects compare as
+/// sorted `(key, value)` sequences. A missing field indexes as null.
+#[derive(Debug, Clone)]
+pub struct IndexKey(pub Vec<Value>);
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_sequences(&self.0, &other.0)
+    }
+}
+
+pub fn compare_values(left: &Value, right: &Value) -> Ordering {
+    match (left, right) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Bool(left), Value::Bool(right)) => left.cmp(right),
+        (Value::Number(left), Value::Number(right)) => compare_numbers(left, right),
+        (Value::String(left), Value::String(right)) => left.cmp(right),
+        (Value::Array(left), Value::Array(right)) => compare_sequences(left, right),
+        (Value::Object(left), Value::Object(right)) => {
+            let mut left_entries = left.iter();
+            let mut right_entries = right.iter();
+            loop {
+                match (left_entries.next(), right_entries.next()) {
+                    (None, None) => return Ordering::Equal,
+                    (None, Some(_)) => return Ordering::Less,
+                    (Some(_), None) => return Ordering::Greater,
+                    (Some((left_key, left_value)), Some((right_key, right_value))) => {
+                        let ordering = left_key
+                            .cmp(right_key)
+                            .then_with(|| compare_values(left_value, right_value));
+                        if ordering != Ordering::Equal {
+                            return ordering;
+                        }
+                    }
+                }
+            }
+        }
+        _ => type_rank(left).cmp(&type_rank(right)),
+    }
+}
+
+/// Compares numbers by exact value. Integers and floats are never converted
+/// to a common lossy type, so the order stays transitive above 2^53.
+fn compare_numbers(left: &serde_json::Number, right: &serde_json::Number) -> Ordering {
+    match (as_integer(left), as_integer(right)) {
+        (Some(left), Some(right)) => left.cmp(&right),
+        (Some(left), None) => compare_integer_float(left, right.as_f64().unwrap_or(f64::NAN)),
+        (None, Some(right)) => {
+            compare_integer_float(right, left.as_f64().unwrap_or(f64::NAN)).reverse()
+        }
+        (None, None) => {
+            let left = left.as_f64().unwrap_or(f64::NAN);
+            let right = right.as_f64().unwrap_or(f64::NAN);
+            // `partial_cmp` keeps -0.0 equal to 0.0, and so to the integer 0.
+            left.partial_cmp(&right)
+                .unwrap_or_else(|| left.total_cmp(&right))
+        }
+    }
+}
+
+fn as_integer(number: &serde_json::Number) -> Option<i128> {
+    number
+        .as_i64()
+        .map(i128::from)
+        .or_else(|| number.as_u64().map(i128::from))
+}
+
+fn compare_integer_float(integer: i128, float: f64) -> Ordering {
+    // 2^127: every i64 and u64 lies strictly inside (-BOUND, BOUND).
+    const BOUND: f64 = 170_141_183_460_469_231_731_687_303_715_884_105_728.0;
+    if float.is_nan() || float >= BOUND {
+        return Ordering::Less;
+    }
+    if float < -BOUND {
+        return Ordering::Greater;
+    }
+
+    // An integral float below 2^127 in magnitude converts to i128 exactly.
+    let whole = float.trunc();
+    integer.cmp(&(whole as i128)).then_with(|| {
+        if float > whole {
+            Ordering::Less
+        } else if float < whole {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    })
+}
+
+fn compare_sequences(left: &[Value], right: &[Value]) -> Ordering {
+    for (left, right) in left.iter().zip(right) {
+        let ordering = compare_values(left, right);
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    left.len().cmp(&right.len())
+}
+
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Index {
+    definition: Arc<IndexDefinition>,
+    // Ids are shared with the owning table's key, so every index on a table
+    // points at the same allocation for a given document.
+    entries: BTreeMap<IndexKey, BTreeSet<(IndexKey, Arc<str>)>>,
+}
+
+impl Index {
+    pub fn new(definition: IndexDefinition) -> Self {
+        Self::sharing(Arc::new(definition))
+    }
+
+    fn sharing(definition: Arc<IndexDefinition>) -> Self {
+        Self {
+            definition,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn definition(&self) -> &IndexDefinition {
+        &self.definition
+    }
+
+    /// An index with the same (shared) definition and no entries.
+    pub fn empty_copy(&self) -> Self {
+        Self::sharing(Arc::clone(&self.definition))
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn stats(&self) -> IndexStats {
+        let key_size = |key: &IndexKey| key.0.iter().map(estimated_value_size).sum::<usize>();
+        let mut stats = IndexStats {
+            name: self.definition.name.clone(),
+            entry_count: 0,
+            estimated_bytes: 0,
+        };
+        for (key, bucket) in &self.entries {
+            stats.entry_count += bucket.len();
+            stats.estimated_bytes += key_size(key)
+                + bucket
+                    .iter()
+                    .map(|(sort_key, id)| key_size(sort_key) + id.len())
+                    .sum::<usize>();
+        }
+        stats
+    }
+
+    pub fn key_for(&self, fields: &BTreeMap<String, Value>) -> IndexKey {
+        IndexKey(
+            self.definition
+                .fields
+                .iter()
+                .map(|field| fields.get(field).cloned().unwrap_or(Value::Null))
+                .collect(),
+        )
+    }
+
+    /// Every key the document is filed under: just `key_for` unless the index
+    /// is multikey, in which case arrays expand to one key per element.
+    fn keys_for(&self, fields: &BTreeMap<String, Value>) -> BTreeSet<IndexKey> {
+        if !self.definition.multikey {
+            return BTreeSet::from([self.key_for(fields)]);
+        }
+
+        let mut keys = vec![Vec::new()];
+        for field in &self.definition.fields {
+            let values = match fields.get(field) {
+                Some(Value::Array(items)) if !items.is_empty() => items.as_slice(),
+                Some(value) => std::slice::from_ref(value),
+                None => &[Value::Null],
+            };
+            keys = keys
+                .into_iter()
+                .flat_map(|prefix: Vec<Value>| {
+                    values.iter().map(move |value| {
+                        let mut key = prefix.clone();
+                        key.push(value.clone());
+                        key
+                    })
+                })
+                .collect();
+        }
+        keys.into_iter().map(IndexKey).collect()
+    }
+
+    /// Drops repeat ids from a walk over several keys, which only a multikey
+    /// index can produce.
+    fn distinct<'a>(&self, ids: impl Iterator<Item = &'a str>) -> impl Iterator<Item = &'a str> {
+        let mut seen = self.definition.multikey.then(HashSet::new);
+        ids.filter(move |id| seen.as_mut().is_none_or(|seen| seen.insert(*id)))
+    }
+
+    /// Inserts many documents at once. With the `rayon` feature, chunks are
+    /// indexed on worker threads and merged, which helps on large tables.
+    pub fn backfill<'a>(
+        &mut self,
+        documents: impl Iterator<Item = (&'a Arc<str>, &'a BTreeMap<String, Value>)>,
+    ) {
+        #[cfg(not(feature = "rayon"))]
+        for (id, fields) in documents {
+            self.insert(id, fields);
+        }
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+
+            const CHUNK: usize = 4_096;
+            let documents: Vec<_> = documents.collect();
+            let partials: Vec<Index> = documents
+                .par_chunks(CHUNK)
+                .map(|chunk| {
+                    let mut partial = Index::sharing(Arc::clone(&self.definition));
+                    for (id, fields) in chunk {
+                        partial.insert(id, fields);
+                    }
+                    partial
+                })
+                .collect();
+            for partial in partials {
+                for (key, bucket) in partial.entries {
+                    self.entries.entry(key).or_default().extend(bucket);
+                }
+            }
+        }
+    }
+
+    fn sort_key_for(&self, fields: &BTreeMap<String, Value>) -> IndexKey {
+        IndexKey(
+            self.definition
+                .sort_by
+                .iter()
+                .map(|field| fields.get(field).cloned().unwrap_or(Value::Null))
+                .collect(),
+        )
+    }
+
+    pub fn insert(&mut self, id: &Arc<str>, fields: &BTreeMap<String, Value>) {
+        let sort_key = self.sort_key_for(fields);
+        for key in self.keys_for(fields) {
+            self.entries
+                .entry(key)
+                .or_default()
+                .insert((sort_key.clone(), Arc::clone(id)));
+        }
+    }
+
+    pub fn remove(&mut self, id: &Arc<str>, fields: &BTreeMap<String, Value>) {
+        let entry = (self.sort_key_for(fields), Arc::clone(id));
+        for key in self.keys_for(fields) {
+            if let Some(bucket) = self.entries.get_mut(&key) {
+                bucket.remove(&entry);
+                if bucket.is_empty() {
+                    self.entries.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// Moves `before`'s entry to `after`'s, or leaves the index untouched when
+    /// none of the key or sort fields differ between the two.
+    pub fn update(
+        &mut self,
+        id: &Arc<str>,
+        before: &BTreeMap<String, Value>,
+        after: &BTreeMap<String, Value>,
+    ) {
+        if self.same_entry(before, after) {
+            return;
+        }
+        self.remove(id, before);
+        self.insert(id, after);
+    }
+
+    fn same_entry(
+        &self,
+        before: &BTreeMap<String, Value>,
+        after: &BTreeMap<String, Value>,
+    ) -> bool {
+        self.definition
+            .fields
+            .iter()
+            .chain(&self.definition.sort_by)
+            .all(|field| before.get(field) == after.get(field))
+    }
+
+    fn check_arity(&self, values: &[Value]) -> CoreResult<()> {
+        if values.len() != self.definition.fields.len() {
+            return Err(CoreError::InvalidOperation(format!(
+                "index {} expects {} values, got {}",
+                self.definition.name,
+                self.definition.fields.len(),
+                values.len()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn lookup(&self, values: &[Value]) -> CoreResult<impl Iterator<Item = &str>> {
+        self.check_arity(values)?;
+
+        Ok(self
+            .entries
+            .get(&IndexKey(values.to_vec()))
+            .into_iter()
+            .flatten()
+            .map(|(_, id)| id.as_ref()))
+    }
+
+    /// Matches every key that starts with `prefix`, treating the remaining
+    /// fields as wildcards. A full-length prefix is a plain `lookup`.
+    pub fn lookup_prefix(&self, prefix: &[Value]) -> CoreResult<impl Iterator<Item = &str>> {
+        if prefix.len() > self.definition.fields.len() {
+            return Err(CoreError::InvalidOperation(format!(
+                "index {} has {} fields, got {} values",
+                self.definition.name,
+                self.definition.fields.len(),
+                prefix.len()
+            )));
+        }
+
+        // A prefix sorts before all of its extensions, so the matches are the
+        // contiguous run of keys starting at it.
+        let prefix = prefix.to_vec();
+        let start = IndexKey(prefix.clone());
+        Ok(self.distinct(
+            self.entries
+                .range((Bound::Included(start), Bound::Unbounded))
+                .take_while(move |(key, _)| {
+                    compare_sequences(&key.0[..prefix.len()], &prefix) == Ordering::Equal
+                })
+                .flat_map(|(_, bucket)| bucket.iter().map(|(_, id)| id.as_ref())),
+        ))
+    }
+
+    /// Lazily walks every id whose key falls between the bounds, in key order
+    /// and then bucket order. The bound keys are copied once per call; nothing
+    /// is collected, so callers can stop after the first few ids.
+    pub fn range_iter(
+        &self,
+        lower: Bound<&[Value]>,
+        upper: Bound<&[Value]>,
+        skip_null: bool,
+    ) -> CoreResult<impl Iterator<Item = &str>> {
+        let to_key = |bound: Bound<&[Value]>| -> CoreResult<Bound<IndexKey>> {
+            Ok(match bound {
+                Bound::Included(values) => {
+                    self.check_arity(values)?;
+                    Bound::Included(IndexKey(values.to_vec()))
+                }
+                Bound::Excluded(values) => {
+                    self.check_arity(values)?;
+                    Bound::Excluded(IndexKey(values.to_vec()))
+                }
+                Bound::Unbounded => Bound::Unbounded,
+            })
+        };
+        let lower = to_key(lower)?;
+        let upper = to_key(upper)?;
+
+        // BTreeMap::range panics on inverted or empty-exclusive ranges.
+        let empty = match (&lower, &upper) {
+            (Bound::Included(start), Bound::Included(end)) => start > end,
+            (
+                Bound::Included(start) | Bound::Excluded(start),
+                Bound::Included(end) | Bound::Excluded(end),
+            ) => start >= end,
+            _ => false,
+        };
+        let range = (!empty).then(|| self.entries.range((lower, upper)));
+
+        // Null sorts before every other value, so null-keyed entries are a
+        // prefix of any range and can be skipped from the front.
+        Ok(self.distinct(
+            range
+                .into_iter()
+                .flatten()
+                .skip_while(move |(key, _)| skip_null && key.0.first() == Some(&Value::Null))
+                .flat_map(|(_, bucket)| bucket.iter().map(|(_, id)| id.as_ref())),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare_values, Index, IndexDefinition, IndexKey};
+    use serde_json::json;
+    use std::cmp::Ordering;
+    use std::collections::BTreeMap;
+    use std::ops::Bound;
+    use std::sync::Arc;
+
+    #[test]
+    fn orders_mixed_types_by_rank_then_value() {
+        let mut keys = vec![
+            json!({"a": 1}),
+            json!("b"),
+            json!([1, 2]),
+            json!(2.5),
+            json!(true),
+            json!(null),
+            json!("a"),
+            json!(-3),
+            json!(false),
+        ];
+        keys.sort_by(compare_values);
+        assert_eq!(
+            keys,
+            vec![
+                json!(null),
+                json!(false),
+                json!(true),
+                json!(-3),
+                json!(2.5),
+                json!("a"),
+                json!("b"),
+                json!([1, 2]),
+                json!({"a": 1}),
+            ]
+        );
+    }
+
+    #[test]
+    fn integer_and_float_forms_are_the_same_key() {
+        assert_eq!(compare_values(&json!(1), &json!(1.0)), Ordering::Equal);
+        assert_eq!(IndexKey(vec![json!(1)]), IndexKey(vec![json!(1.0)]));
+        assert_eq!(
+            compare_values(&json!(u64::MAX), &json!(i64::MAX)),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn update_skips_only_when_key_and_sort_fields_match() {
+        let document = |fields: serde_json::Value| {
+            serde_json::from_value::<BTreeMap<String, serde_json::Value>>(fields).expect("object")
+        };
+        let id: Arc<str> = Arc::from("d_1");
+        let index = Index::new(IndexDefinition {
+            name: "by_status".to_string(),
+            fields: vec!["status".to_string()],
+            sort_by: Some("rank".to_string()),
+            multikey: false,
+        });
+
+        let base = document(json!({"status": "open", "rank": 1, "title": "a"}));
+        let same = |after: serde_json::Value| index.same_entry(&base, &document(after));
+
+        assert!(same(json!({"status": "open", "rank": 1, "title": "b"})));
+        assert!(!same(json!({"status": "done", "rank": 1, "title": "a"})));
+        assert!(!same(json!({"status": "open", "rank": 2, "title": "a"})));
+        assert!(!same(json!({"rank": 1, "title": "a"})));
+        assert!(!same(json!({"status": null, "rank": 1, "title": "a"})));
+
+        let mut index = index.clone();
+        let null_status = document(json!({"status": null}));
+        let missing_status = document(json!({}));
+        index.insert(&id, &null_status);
+        index.update(&id, &null_status, &missing_status);
+        assert_eq!(index.lookup(&[json!(null)]).expect("lookup").count(), 1);
+
+        let moved = document(json!({"status": "open"}));
+        index.update(&id, &missing_status, &moved);
+        assert_eq!(index.lookup(&[json!(null)]).expect("lookup").count(), 0);
+        assert_eq!(index.lookup(&[json!("open")]).expect("lookup").count(), 1);
+    }
+
+    #[test]
+    fn backfill_matches_one_by_one_inserts() {
+        let definition = IndexDefinition {
+            name: "by_bucket".to_string(),
+            fields: vec!["bucket".to_string()],
+            sort_by: Some("rank".to_string()),
+            multikey: false,
+        };
+        let documents: Vec<(Arc<str>, BTreeMap<String, serde_json::Value>)> = (0..10_000)
+            .map(|n| {
+                let fields = serde_json::from_value(json!({"bucket": n % 7, "rank": n % 13}))
+                    .expect("object");
+                (Arc::from(format!("d_{n}")), fields)
+            })
+            .collect();
+
+        let mut expected = Index::new(definition.clone());
+        for (id, fields) in &documents {
+            expected.insert(id, fields);
+        }
+        let mut backfilled = Index::new(definition);
+        backfilled.backfill(documents.iter().map(|(id, fields)| (id, fields)));
+
+        assert_eq!(backfilled, expected);
+        assert_eq!(
+            backfilled.lookup(&[json!(3)]).expect("lookup").count(),
+            expected.lookup(&[json!(3)]).expect("lookup").count()
+        );
+    }
+
+    #[test]
+    fn multikey_files_each_element_and_walks_ids_once() {
+        let mut definition = IndexDefinition::new("by_tags", &["tags"]);
+        definition.multikey = true;
+        let mut index = Index::new(definition);
+        let id: Arc<str> = Arc::from("d_1");
+        let fields: BTreeMap<String, serde_json::Value> =
+            serde_json::from_value(json!({"tags": [1, "b", 1.0, "c"]})).expect("object");
+
+        index.insert(&id, &fields);
+        assert_eq!(index.entries.len(), 3);
+        assert_eq!(index.lookup(&[json!(1)]).expect("lookup").count(), 1);
+        assert_eq!(index.lookup(&[json!("c")]).expect("lookup").count(), 1);
+        let walked: Vec<&str> = index
+            .range_iter(Bound::Unbounded, Bound::Unbounded, false)
+            .expect("range")
+            .collect();
+        assert_eq!(walked, vec!["d_1"]);
+        assert_eq!(index.lookup_prefix(&[]).expect("prefix").count(), 1);
+
+        index.remove(&id, &fields);
+        assert!(index.entries.is_empty());
+    }
+
+    #[test]
+    fn large_integers_compare_exactly_against_floats() {
+        let two_53 = 1_i64 << 53;
+        let float = json!(9_007_199_254_740_992.0);
+        assert_eq!(compare_values(&json!(two_53), &float), Ordering::Equal);
+        assert_eq!(
+            compare_values(&json!(two_53 + 1), &float),
+            Ordering::Greater
+        );
+        assert_eq!(compare_values(&float, &json!(two_53 + 1)), Ordering::Less);
+        assert_eq!(
+            compare_values(&json!(i64::MAX), &json!(9.3e18)),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_values(&json!(u64::MAX), &json!(1.8e19)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_values(&json!(u64::MAX), &json!(1e300)),
+            Ordering::Less
+        );
+        assert_eq!(compare_values(&json!(-3), &json!(-2.5)), Ordering::Less);
+        assert_eq!(compare_values(&json!(2), &json!(2.5)), Ordering::Less);
+        assert_eq!(compare_values(&json!(0), &json!(-0.0)), Ordering::Equal);
+        assert_eq!(compare_values(&json!(0.0), &json!(-0.0)), Ordering::Equal);
+    }
+}
@@ -1,13 +1,38 @@
+// Library code reports broken invariants as CoreError::Internal instead of
+// panicking; tests may still unwrap.
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used))]
+
+#[cfg(feature = "async")]
+pub mod actor;
 pub mod engine;
 pub mod error;
+pub mod ids;
+pub mod index;
+pub mod metrics;
+pub mod migration;
+pub mod namespace;
 pub mod schema;
+pub mod shared;
+pub mod stats;
+mod trace;
 pub mod types;
 
+#[cfg(feature = "async")]
+pub use actor::{Command, EngineActor, EngineHandle};
 pub use engine::InMemoryEngine;
 pub use error::{CoreError, CoreResult};
+pub use ids::{IdGenerator, SequentialIds, Table, TypedId, UuidV7Ids};
+pub use index::{IndexDefinition, IndexKey, RangeOptions};
+pub use metrics::{InMemoryMetrics, Metrics, NoopMetrics, OpKind, OpMetrics, Outcome};
+pub use migration::{MigrationFailure, MigrationReport, Migrations};
+pub use namespace::{EngineSet, NamespaceState, NamespaceTemplate};
 pub use schema::{
     Schema, SchemaField, SchemaType, WireCollectionSchema, WireDatabaseSchema, WireSchemaField,
+    WireSchemaType,
 };
+pub use shared::SharedEngine;
+pub use stats::{EngineStats, IndexStats, OperationCounts, TableStats};
 pub use types::{
-    Document, DocumentId, NewDocument, Revision, TableName, TableState, Value, WriteOperation,
+    Backup, CopyTableOptions, Document, DocumentId, HealthState, NewDocument, Quota, Revision,
+    TableDiff, TableName, TableSnapshot, TableState, TtlPolicy, Value, WriteOperation, AUDIT_TABLE,
 };
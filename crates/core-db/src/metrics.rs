@@ -0,0 +1,141 @@
+use crate::error::{CoreError, CoreResult};
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Write};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OpKind {
+    CreateTable,
+    DropTable,
+    CreateIndex,
+    Get,
+    List,
+    Count,
+    Scan,
+    IndexQuery,
+    WriteBatch,
+    UpdateSchema,
+    CopyTable,
+    Import,
+    Restore,
+    RebuildIndex,
+    SetAuditLog,
+    PruneAudit,
+    SetQuota,
+    SetTtl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    Success,
+    NotFound,
+    SchemaViolation,
+    QuotaExceeded,
+    Conflict,
+    Error,
+}
+
+impl Outcome {
+    pub fn of<T>(result: &CoreResult<T>) -> Self {
+        match result.as_ref().map_err(CoreError::root) {
+            Ok(_) => Self::Success,
+            Err(
+                CoreError::TableNotFound { .. }
+                | CoreError::DocumentNotFound { .. }
+                | CoreError::IndexNotFound(_),
+            ) => Self::NotFound,
+            Err(CoreError::SchemaViolation { .. }) => Self::SchemaViolation,
+            Err(CoreError::QuotaExceeded(_)) => Self::QuotaExceeded,
+            Err(CoreError::RevisionMismatch { .. }) => Self::Conflict,
+            Err(_) => Self::Error,
+        }
+    }
+}
+
+pub trait Metrics: Debug + Send + Sync {
+    fn record(&self, op: OpKind, table: &str, duration: Duration, outcome: Outcome);
+}
+
+#[derive(Debug, Default)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {
+    fn record(&self, _op: OpKind, _table: &str, _duration: Duration, _outcome: Outcome) {}
+}
+
+const LATENCY_BUCKETS_MICROS: [u64; 5] = [10, 100, 1_000, 10_000, 100_000];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpMetrics {
+    pub count: u64,
+    pub total_micros: u64,
+    // One counter per LATENCY_BUCKETS_MICROS bound plus a final overflow bucket.
+    pub buckets: [u64; LATENCY_BUCKETS_MICROS.len() + 1],
+}
+
+#[derive(Debug, Default)]
+pub struct InMemoryMetrics {
+    entries: Mutex<BTreeMap<(OpKind, String, Outcome), OpMetrics>>,
+}
+
+impl InMemoryMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, op: OpKind, table: &str, outcome: Outcome) -> Option<OpMetrics> {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&(op, table.to_owned(), outcome))
+            .cloned()
+    }
+
+    pub fn count(&self, op: OpKind, outcome: Outcome) -> u64 {
+        self.entries
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .filter(|((entry_op, _, entry_outcome), _)| {
+                *entry_op == op && *entry_outcome == outcome
+            })
+            .map(|(_, metrics)| metrics.count)
+            .sum()
+    }
+
+    pub fn render(&self) -> String {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut output = String::new();
+        for ((op, table, outcome), metrics) in entries.iter() {
+            let _ = writeln!(
+                output,
+                "op={:?} table={} outcome={:?} count={} total_us={} buckets={:?}",
+                op, table, outcome, metrics.count, metrics.total_micros, metrics.buckets
+            );
+        }
+        output
+    }
+}
+
+impl Metrics for InMemoryMetrics {
+    fn record(&self, op: OpKind, table: &str, duration: Duration, outcome: Outcome) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let bucket = LATENCY_BUCKETS_MICROS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(LATENCY_BUCKETS_MICROS.len());
+
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let metrics = entries.entry((op, table.to_owned(), outcome)).or_default();
+        metrics.count += 1;
+        metrics.total_micros = metrics.total_micros.saturating_add(micros);
+        metrics.buckets[bucket] += 1;
+    }
+}
+
+impl fmt::Display for InMemoryMetrics {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(&self.render())
+    }
+}
@@ -0,0 +1,372 @@
+use crate::engine::InMemoryEngine;
+use crate::error::CoreResult;
+use crate::types::{NewDocument, Value, WriteOperation};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+pub const MIGRATIONS_TABLE: &str = "_migrations";
+
+pub type MigrationStep = Box<dyn Fn(&mut InMemoryEngine) -> CoreResult<()>>;
+
+pub struct Migration {
+    pub id: String,
+    pub name: String,
+    step: MigrationStep,
+}
+
+#[derive(Default)]
+pub struct Migrations {
+    steps: Vec<Migration>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct MigrationFailure {
+    pub id: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct MigrationReport {
+    pub applied: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Option<MigrationFailure>,
+}
+
+impl Migrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(
+        mut self,
+        id: &str,
+        name: &str,
+        step: impl Fn(&mut InMemoryEngine) -> CoreResult<()> + 'static,
+    ) -> Self {
+        self.steps.push(Migration {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            step: Box::new(step),
+        });
+        self
+    }
+}
+
+impl InMemoryEngine {
+    /// Runs unapplied migrations in order. Each one runs against a working copy
+    /// whose tables only replace the live ones once the step and its
+    /// `_migrations` record both succeed, so a failing step leaves no partial
+    /// writes behind. The engine keeps its own id generator and settings.
+    /// `_migrations` is only written here; user writes and drops are rejected.
+    pub fn run_migrations(&mut self, migrations: &Migrations) -> CoreResult<MigrationReport> {
+        self.create_system_table(MIGRATIONS_TABLE)?;
+
+        let mut report = MigrationReport::default();
+        for migration in &migrations.steps {
+            if self.contains(MIGRATIONS_TABLE, &migration.id)? {
+                report.skipped.push(migration.id.clone());
+                continue;
+            }
+
+            let mut working = self.working_copy();
+            let outcome = (migration.step)(&mut working).and_then(|()| {
+                let mut fields = BTreeMap::new();
+                fields.insert("name".to_string(), Value::String(migration.name.clone()));
+                working.write_system_batch(
+                    MIGRATIONS_TABLE,
+                    &[WriteOperation::Put(NewDocument {
+                        id: Some(migration.id.clone()),
+                        fields,
+                    })],
+                )
+            });
+
+            match outcome {
+                Ok(_) => {
+                    self.adopt_tables(working);
+                    report.applied.push(migration.id.clone());
+                }
+                Err(error) => {
+                    report.failed = Some(MigrationFailure {
+                        id: migration.id.clone(),
+                        error: error.to_string(),
+                    });
+                    break;
+                }
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+pub fn add_field_with_default(
+    table: &str,
+    field: &str,
+    default: Value,
+) -> impl Fn(&mut InMemoryEngine) -> CoreResult<()> {
+    let table = table.to_owned();
+    let field = field.to_owned();
+    move |engine| {
+        rewrite_documents(engine, &table, |fields| {
+            if fields.contains_key(&field) {
+                return Ok(false);
+            }
+            fields.insert(field.clone(), default.clone());
+            Ok(true)
+        })
+    }
+}
+
+pub fn rename_field(
+    table: &str,
+    from: &str,
+    to: &str,
+) -> impl Fn(&mut InMemoryEngine) -> CoreResult<()> {
+    let table = table.to_owned();
+    let from = from.to_owned();
+    let to = to.to_owned();
+    move |engine| {
+        rewrite_documents(engine, &table, |fields| match fields.remove(&from) {
+            Some(value) => {
+                fields.insert(to.clone(), value);
+                Ok(true)
+            }
+            None => Ok(false),
+        })
+    }
+}
+
+pub fn drop_field(table: &str, field: &str) -> impl Fn(&mut InMemoryEngine) -> CoreResult<()> {
+    let table = table.to_owned();
+    let field = field.to_owned();
+    move |engine| rewrite_documents(engine, &table, |fields| Ok(fields.remove(&field).is_some()))
+}
+
+pub fn change_field_type(
+    table: &str,
+    field: &str,
+    converter: impl Fn(&Value) -> CoreResult<Value> + 'static,
+) -> impl Fn(&mut InMemoryEngine) -> CoreResult<()> {
+    let table = table.to_owned();
+    let field = field.to_owned();
+    move |engine| {
+        rewrite_documents(engine, &table, |fields| match fields.get_mut(&field) {
+            Some(value) => {
+                *value = converter(value)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        })
+    }
+}
+
+fn rewrite_documents(
+    engine: &mut InMemoryEngine,
+    table: &str,
+    mut rewrite: impl FnMut(&mut BTreeMap<String, Value>) -> CoreResult<bool>,
+) -> CoreResult<()> {
+    let mut ops = Vec::new();
+    for document in engine.list_documents(table)? {
+        let mut fields = document.fields;
+        if rewrite(&mut fields)? {
+            ops.push(WriteOperation::Put(NewDocument {
+                id: Some(document.id),
+                fields,
+            }));
+        }
+    }
+
+    if ops.is_empty() {
+        return Ok(());
+    }
+    engine.write_batch(table, &ops)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        add_field_with_default, change_field_type, drop_field, rename_field, Migrations,
+        MIGRATIONS_TABLE,
+    };
+    use crate::engine::InMemoryEngine;
+    use crate::error::CoreError;
+    use crate::ids::{IdGenerator, SequentialIds};
+    use crate::schema::Schema;
+    use crate::types::{NewDocument, WriteOperation};
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn seeded_engine() -> InMemoryEngine {
+        let mut engine = InMemoryEngine::new();
+        engine
+            .create_table("users", Schema::default())
+            .expect("table should be created");
+        engine
+            .insert_typed("users", &json!({ "fullName": "Ada", "age": "36" }))
+            .expect("insert should succeed");
+        engine
+            .insert_typed(
+                "users",
+                &json!({ "fullName": "Lin", "age": "29", "legacy": true }),
+            )
+            .expect("insert should succeed");
+        engine
+    }
+
+    fn migrations() -> Migrations {
+        Migrations::new()
+            .add(
+                "001",
+                "add active flag",
+                add_field_with_default("users", "active", json!(true)),
+            )
+            .add(
+                "002",
+                "rename fullName",
+                rename_field("users", "fullName", "name"),
+            )
+            .add("003", "drop legacy", drop_field("users", "legacy"))
+            .add(
+                "004",
+                "age to number",
+                change_field_type("users", "age", |value| {
+                    value
+                        .as_str()
+                        .and_then(|text| text.parse::<u64>().ok())
+                        .map(|age| json!(age))
+                        .ok_or_else(|| {
+                            CoreError::InvalidOperation("age is not numeric".to_string())
+                        })
+                }),
+            )
+    }
+
+    #[test]
+    fn runs_pending_migrations_once() {
+        let mut engine = seeded_engine();
+
+        let report = engine
+            .run_migrations(&migrations())
+            .expect("migrations should run");
+        assert_eq!(report.applied, vec!["001", "002", "003", "004"]);
+        assert!(report.failed.is_none());
+
+        for document in engine.list_documents("users").expect("list") {
+            assert_eq!(document.fields.get("active"), Some(&json!(true)));
+            assert!(document
+                .fields
+                .get("name")
+                .is_some_and(|name| name.is_string()));
+            assert!(document
+                .fields
+                .get("age")
+                .is_some_and(|age| age.is_number()));
+            assert!(!document.fields.contains_key("fullName"));
+            assert!(!document.fields.contains_key("legacy"));
+        }
+
+        let rerun = engine
+            .run_migrations(&migrations())
+            .expect("migrations should run");
+        assert!(rerun.applied.is_empty());
+        assert_eq!(rerun.skipped.len(), 4);
+    }
+
+    #[test]
+    fn stops_on_failure_without_partial_writes() {
+        let mut engine = seeded_engine();
+        let before = engine.list_documents("users").expect("list");
+
+        let failing = Migrations::new()
+            .add(
+                "001",
+                "add active flag",
+                add_field_with_default("users", "active", json!(true)),
+            )
+            .add("002", "broken", |engine: &mut InMemoryEngine| {
+                add_field_with_default("users", "score", json!(0))(engine)?;
+                Err(CoreError::InvalidOperation("boom".to_string()))
+            })
+            .add("003", "drop legacy", drop_field("users", "legacy"));
+
+        let report = engine
+            .run_migrations(&failing)
+            .expect("migrations should run");
+        assert_eq!(report.applied, vec!["001"]);
+        assert_eq!(
+            report.failed.map(|failure| failure.id),
+            Some("002".to_string())
+        );
+
+        for (document, original) in engine
+            .list_documents("users")
+            .expect("list")
+            .iter()
+            .zip(&before)
+        {
+            assert!(!document.fields.contains_key("score"));
+            assert_eq!(document.fields.get("legacy"), original.fields.get("legacy"));
+        }
+        assert!(engine.contains("_migrations", "001").expect("table exists"));
+        assert!(!engine.contains("_migrations", "002").expect("table exists"));
+    }
+
+    #[test]
+    fn keeps_the_injected_id_generator_and_guards_the_migrations_table() {
+        let ids = Arc::new(SequentialIds::new());
+        let mut engine = InMemoryEngine::new();
+        engine.set_id_generator(ids.clone());
+        engine
+            .create_table("users", Schema::default())
+            .expect("table should be created");
+        engine
+            .insert_typed("users", &json!({ "name": "Ada" }))
+            .expect("insert should succeed");
+
+        let seeding = Migrations::new().add("001", "seed", |engine: &mut InMemoryEngine| {
+            engine
+                .insert_typed("users", &json!({ "name": "Lin" }))
+                .map(drop)
+        });
+        engine
+            .run_migrations(&seeding)
+            .expect("migrations should run");
+        assert!(engine.contains("users", "users:2").expect("table exists"));
+        let after = engine
+            .insert_typed("users", &json!({ "name": "Bo" }))
+            .expect("insert should succeed");
+        assert_eq!(after.id, "users:3");
+        assert_eq!(ids.generate("users"), "users:4");
+
+        let rejected = |error: CoreError| matches!(error.root(), CoreError::InvalidOperation(_));
+        assert!(rejected(
+            engine
+                .write_batch(
+                    MIGRATIONS_TABLE,
+                    &[WriteOperation::Delete("001".to_string())]
+                )
+                .expect_err("user writes are rejected")
+        ));
+        assert!(rejected(
+            engine
+                .write_batch(
+                    MIGRATIONS_TABLE,
+                    &[WriteOperation::Put(NewDocument {
+                        id: Some("002".to_string()),
+                        fields: Default::default(),
+                    })],
+                )
+                .expect_err("user writes are rejected")
+        ));
+        assert!(rejected(
+            engine
+                .drop_table(MIGRATIONS_TABLE)
+                .expect_err("drops are rejected")
+        ));
+        assert!(engine
+            .contains(MIGRATIONS_TABLE, "001")
+            .expect("table exists"));
+    }
+}
@@ -0,0 +1,89 @@
+use crate::engine::InMemoryEngine;
+use crate::error::{CoreError, CoreResult};
+use crate::index::IndexDefinition;
+use crate::schema::WireDatabaseSchema;
+use crate::stats::EngineStats;
+use crate::types::TableName;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct NamespaceState {
+    pub name: String,
+    pub stats: EngineStats,
+}
+
+/// What every new namespace starts with: the tables of `schema`, each with
+/// the indexes listed for it in `indexes`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NamespaceTemplate {
+    pub schema: WireDatabaseSchema,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub indexes: BTreeMap<TableName, Vec<IndexDefinition>>,
+}
+
+impl From<WireDatabaseSchema> for NamespaceTemplate {
+    fn from(schema: WireDatabaseSchema) -> Self {
+        Self {
+            schema,
+            indexes: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EngineSet {
+    namespaces: BTreeMap<String, InMemoryEngine>,
+}
+
+impl EngineSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn create_namespace(
+        &mut self,
+        name: &str,
+        template: &NamespaceTemplate,
+    ) -> CoreResult<&mut InMemoryEngine> {
+        if self.namespaces.contains_key(name) {
+            return Err(CoreError::NamespaceAlreadyExists(name.to_owned()));
+        }
+
+        let mut engine = InMemoryEngine::from_schema(&template.schema)?;
+        for (table, definitions) in &template.indexes {
+            for definition in definitions {
+                engine.create_index_with(table, definition.clone())?;
+            }
+        }
+        Ok(self.namespaces.entry(name.to_owned()).or_insert(engine))
+    }
+
+    pub fn namespace(&self, name: &str) -> CoreResult<&InMemoryEngine> {
+        self.namespaces
+            .get(name)
+            .ok_or_else(|| CoreError::NamespaceNotFound(name.to_owned()))
+    }
+
+    pub fn namespace_mut(&mut self, name: &str) -> CoreResult<&mut InMemoryEngine> {
+        self.namespaces
+            .get_mut(name)
+            .ok_or_else(|| CoreError::NamespaceNotFound(name.to_owned()))
+    }
+
+    pub fn list_namespaces(&self) -> Vec<NamespaceState> {
+        self.namespaces
+            .iter()
+            .map(|(name, engine)| NamespaceState {
+                name: name.clone(),
+                stats: engine.stats(),
+            })
+            .collect()
+    }
+
+    pub fn drop_namespace(&mut self, name: &str) -> CoreResult<InMemoryEngine> {
+        self.namespaces
+            .remove(name)
+            .ok_or_else(|| CoreError::NamespaceNotFound(name.to_owned()))
+    }
+}
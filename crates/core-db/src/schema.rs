@@ -1,6 +1,6 @@
 use crate::error::{CoreError, CoreResult};
 use crate::types::Value;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,6 +11,11 @@ pub enum SchemaType {
     Object,
     Array,
     Null,
+    ArrayOf(Box<SchemaType>),
+    ObjectOf(BTreeMap<String, SchemaField>),
+    Union(Vec<SchemaType>),
+    Literal(Value),
+    Id(String),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -24,11 +29,32 @@ pub struct Schema {
     pub fields: BTreeMap<String, SchemaField>,
 }
 
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct WireSchemaField {
     pub required: bool,
+    #[serde(flatten)]
+    pub field_type: WireSchemaType,
+}
+
+/// Recursive wire form of a `SchemaType`. `name` is the `type` string; the
+/// optional keys carry the payload of the compound types: `element` for
+/// `array`, `fields` for `object`, `variants` for `union`, `table` for `id`
+/// and `value` for `literal`. A bare `{"type": "array"}` or
+/// `{"type": "object"}` stays untyped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WireSchemaType {
     #[serde(rename = "type")]
-    pub field_type: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub element: Option<Box<WireSchemaType>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<WireCollectionSchema>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variants: Option<Vec<WireSchemaType>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
 }
 
 pub type WireCollectionSchema = BTreeMap<String, WireSchemaField>;
@@ -40,41 +66,43 @@ impl Schema {
     }
 
     pub fn from_wire(collection: &WireCollectionSchema) -> CoreResult<Self> {
-        let mut fields = BTreeMap::new();
+        Ok(Self {
+            fields: fields_from_wire(None, collection)?,
+        })
+    }
 
-        for (name, wire) in collection {
-            let field_type = SchemaType::try_from(wire.field_type.as_str())?;
-            fields.insert(
-                name.clone(),
-                SchemaField {
-                    required: wire.required,
-                    field_type,
-                },
-            );
-        }
+    pub fn from_wire_table(table: &str, collection: &WireCollectionSchema) -> CoreResult<Self> {
+        Ok(Self {
+            fields: fields_from_wire(Some(table), collection)?,
+        })
+    }
 
-        Ok(Self { fields })
+    pub fn to_wire(&self) -> WireCollectionSchema {
+        fields_to_wire(&self.fields)
     }
 
     pub fn validate(&self, input: &BTreeMap<String, Value>) -> CoreResult<()> {
         for (field_name, field) in &self.fields {
             if field.required && !input.contains_key(field_name) {
-                return Err(CoreError::SchemaViolation(format!(
-                    "missing required field: {}",
-                    field_name
-                )));
+                return Err(CoreError::field_violation(
+                    field_name,
+                    format!("missing required field: {}", field_name),
+                ));
             }
         }
 
         for (key, value) in input {
             if let Some(expected) = self.fields.get(key) {
                 if !matches_schema_type(&expected.field_type, value) {
-                    return Err(CoreError::SchemaViolation(format!(
-                        "field '{}' expected {:?} but got {}",
+                    return Err(CoreError::field_violation(
                         key,
-                        expected.field_type,
-                        value_type_name(value)
-                    )));
+                        format!(
+                            "field '{}' expected {:?} but got {}",
+                            key,
+                            expected.field_type,
+                            value_type_name(value)
+                        ),
+                    ));
                 }
             }
         }
@@ -83,6 +111,152 @@ impl Schema {
     }
 }
 
+impl SchemaType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+            Self::Object | Self::ObjectOf(_) => "object",
+            Self::Array | Self::ArrayOf(_) => "array",
+            Self::Null => "null",
+            Self::Union(_) => "union",
+            Self::Literal(_) => "literal",
+            Self::Id(_) => "id",
+        }
+    }
+
+    pub fn is_scalar(&self) -> bool {
+        match self {
+            Self::Object | Self::Array | Self::ArrayOf(_) | Self::ObjectOf(_) => false,
+            Self::Union(variants) => variants.iter().all(SchemaType::is_scalar),
+            Self::Literal(value) => !value.is_array() && !value.is_object(),
+            Self::String | Self::Number | Self::Boolean | Self::Null | Self::Id(_) => true,
+        }
+    }
+
+    /// `Literal(null)` is written as `{"type": "null"}`, since a null `value`
+    /// key can't be told apart from a missing one on the way back in.
+    pub fn to_wire(&self) -> WireSchemaType {
+        if *self == Self::Literal(Value::Null) {
+            return WireSchemaType::from("null");
+        }
+
+        let mut wire = WireSchemaType::from(self.as_str());
+        match self {
+            Self::ArrayOf(element) => wire.element = Some(Box::new(element.to_wire())),
+            Self::ObjectOf(fields) => wire.fields = Some(fields_to_wire(fields)),
+            Self::Union(variants) => {
+                wire.variants = Some(variants.iter().map(SchemaType::to_wire).collect())
+            }
+            Self::Literal(value) => wire.value = Some(value.clone()),
+            Self::Id(table) => wire.table = Some(table.clone()),
+            _ => {}
+        }
+        wire
+    }
+
+    fn from_wire(path: &str, wire: &WireSchemaType) -> CoreResult<Self> {
+        let missing = |key: &str| {
+            CoreError::schema_violation(format!(
+                "schema type '{}' for {} is missing '{}'",
+                wire.name, path, key
+            ))
+        };
+
+        match wire.name.as_str() {
+            "array" => match &wire.element {
+                Some(element) => Ok(Self::ArrayOf(Box::new(Self::from_wire(
+                    &format!("{}[]", path),
+                    element,
+                )?))),
+                None => Ok(Self::Array),
+            },
+            "object" => match &wire.fields {
+                Some(fields) => Ok(Self::ObjectOf(fields_from_wire(Some(path), fields)?)),
+                None => Ok(Self::Object),
+            },
+            "union" => {
+                let variants = wire.variants.as_ref().ok_or_else(|| missing("variants"))?;
+                if variants.is_empty() {
+                    return Err(CoreError::schema_violation(format!(
+                        "union for {} must have at least one variant",
+                        path
+                    )));
+                }
+                variants
+                    .iter()
+                    .map(|variant| Self::from_wire(path, variant))
+                    .collect::<CoreResult<Vec<_>>>()
+                    .map(Self::Union)
+            }
+            "literal" => wire
+                .value
+                .clone()
+                .map(Self::Literal)
+                .ok_or_else(|| missing("value")),
+            "id" => wire
+                .table
+                .clone()
+                .map(Self::Id)
+                .ok_or_else(|| missing("table")),
+            name => Self::try_from(name).map_err(|_| {
+                CoreError::schema_violation(format!(
+                    "unsupported schema type '{}' for {}",
+                    name, path
+                ))
+            }),
+        }
+    }
+}
+
+impl From<&str> for WireSchemaType {
+    fn from(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+fn fields_from_wire(
+    parent: Option<&str>,
+    collection: &WireCollectionSchema,
+) -> CoreResult<BTreeMap<String, SchemaField>> {
+    let mut fields = BTreeMap::new();
+
+    for (name, wire) in collection {
+        let path = match parent {
+            Some(parent) => format!("{}.{}", parent, name),
+            None => name.clone(),
+        };
+        fields.insert(
+            name.clone(),
+            SchemaField {
+                required: wire.required,
+                field_type: SchemaType::from_wire(&path, &wire.field_type)?,
+            },
+        );
+    }
+
+    Ok(fields)
+}
+
+fn fields_to_wire(fields: &BTreeMap<String, SchemaField>) -> WireCollectionSchema {
+    fields
+        .iter()
+        .map(|(name, field)| {
+            (
+                name.clone(),
+                WireSchemaField {
+                    required: field.required,
+                    field_type: field.field_type.to_wire(),
+                },
+            )
+        })
+        .collect()
+}
+
 impl TryFrom<&str> for SchemaType {
     type Error = CoreError;
 
@@ -94,7 +268,7 @@ impl TryFrom<&str> for SchemaType {
             "object" => Ok(Self::Object),
             "array" => Ok(Self::Array),
             "null" => Ok(Self::Null),
-            _ => Err(CoreError::SchemaViolation(format!(
+            _ => Err(CoreError::schema_violation(format!(
                 "unsupported schema type: {}",
                 value
             ))),
@@ -105,14 +279,41 @@ impl TryFrom<&str> for SchemaType {
 fn matches_schema_type(schema_type: &SchemaType, value: &Value) -> bool {
     match schema_type {
         SchemaType::String => value.is_string(),
+        SchemaType::Id(table) => value.as_str().is_some_and(|id| is_id_for_table(id, table)),
         SchemaType::Number => value.is_number(),
         SchemaType::Boolean => value.is_boolean(),
         SchemaType::Object => value.is_object(),
         SchemaType::Array => value.is_array(),
         SchemaType::Null => value.is_null(),
+        SchemaType::ArrayOf(element) => value
+            .as_array()
+            .is_some_and(|items| items.iter().all(|item| matches_schema_type(element, item))),
+        SchemaType::ObjectOf(fields) => value.as_object().is_some_and(|object| {
+            fields.iter().all(|(name, field)| match object.get(name) {
+                Some(value) => matches_schema_type(&field.field_type, value),
+                None => !field.required,
+            })
+        }),
+        SchemaType::Union(variants) => variants
+            .iter()
+            .any(|variant| matches_schema_type(variant, value)),
+        SchemaType::Literal(literal) => literal == value,
     }
 }
 
+/// True for `<table>:<rest>` where `rest` is nonempty and has no further
+/// `:`, whitespace or control characters, without building the prefix string.
+fn is_id_for_table(id: &str, table: &str) -> bool {
+    id.strip_prefix(table)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .is_some_and(|rest| {
+            !rest.is_empty()
+                && !rest
+                    .chars()
+                    .any(|c| c == ':' || c.is_whitespace() || c.is_control())
+        })
+}
+
 fn value_type_name(value: &Value) -> &'static str {
     if value.is_null() {
         "null"
@@ -131,7 +332,11 @@ fn value_type_name(value: &Value) -> &'static str {
 
 #[cfg(test)]
 mod tests {
-    use super::{Schema, SchemaField, SchemaType, WireCollectionSchema, WireSchemaField};
+    use super::{
+        is_id_for_table, Schema, SchemaField, SchemaType, WireCollectionSchema, WireSchemaField,
+        WireSchemaType,
+    };
+    use serde_json::json;
     use std::collections::BTreeMap;
 
     #[test]
@@ -141,12 +346,13 @@ mod tests {
             "name".to_string(),
             WireSchemaField {
                 required: true,
-                field_type: "string".to_string(),
+                field_type: WireSchemaType::from("string"),
             },
         );
 
         let parsed = Schema::from_wire(&wire).expect("wire schema should parse");
         assert_eq!(parsed.fields.len(), 1);
+        assert_eq!(parsed.to_wire(), wire);
     }
 
     #[test]
@@ -175,4 +381,147 @@ mod tests {
 
         assert!(schema.validate(&bad_doc).is_err());
     }
+
+    #[test]
+    fn round_trips_every_schema_type_through_json_wire() {
+        let field = |required, field_type| SchemaField {
+            required,
+            field_type,
+        };
+        let mut address = BTreeMap::new();
+        address.insert("city".to_string(), field(true, SchemaType::String));
+        address.insert("zip".to_string(), field(false, SchemaType::Number));
+
+        let mut fields = BTreeMap::new();
+        for (name, field_type) in [
+            ("name", SchemaType::String),
+            ("age", SchemaType::Number),
+            ("active", SchemaType::Boolean),
+            ("meta", SchemaType::Object),
+            ("raw", SchemaType::Array),
+            ("nothing", SchemaType::Null),
+            (
+                "tags",
+                SchemaType::ArrayOf(Box::new(SchemaType::ArrayOf(Box::new(SchemaType::String)))),
+            ),
+            ("address", SchemaType::ObjectOf(address)),
+            (
+                "status",
+                SchemaType::Union(vec![
+                    SchemaType::Literal(json!("open")),
+                    SchemaType::Literal(json!(3)),
+                    SchemaType::Null,
+                ]),
+            ),
+            ("owner", SchemaType::Id("users".to_string())),
+        ] {
+            fields.insert(name.to_string(), field(name != "nothing", field_type));
+        }
+        let schema = Schema::with_fields(fields);
+
+        let encoded = serde_json::to_value(schema.to_wire()).expect("wire should serialize");
+        assert_eq!(
+            encoded["tags"],
+            json!({
+                "required": true,
+                "type": "array",
+                "element": {"type": "array", "element": {"type": "string"}}
+            })
+        );
+        assert_eq!(
+            encoded["owner"],
+            json!({"required": true, "type": "id", "table": "users"})
+        );
+        assert_eq!(encoded["name"], json!({"required": true, "type": "string"}));
+
+        let decoded: WireCollectionSchema =
+            serde_json::from_value(encoded).expect("wire should deserialize");
+        assert_eq!(
+            Schema::from_wire(&decoded).expect("wire should parse"),
+            schema
+        );
+
+        let mut doc = BTreeMap::new();
+        doc.insert("name".to_string(), json!("Ada"));
+        doc.insert("age".to_string(), json!(36));
+        doc.insert("active".to_string(), json!(true));
+        doc.insert("meta".to_string(), json!({}));
+        doc.insert("raw".to_string(), json!([1, "x"]));
+        doc.insert("tags".to_string(), json!([["a"], []]));
+        doc.insert("address".to_string(), json!({"city": "London"}));
+        doc.insert("status".to_string(), json!("open"));
+        doc.insert("owner".to_string(), json!("users:1"));
+        assert!(schema.validate(&doc).is_ok());
+
+        doc.insert("status".to_string(), json!("closed"));
+        assert!(schema.validate(&doc).is_err());
+        doc.insert("status".to_string(), json!(null));
+        doc.insert("tags".to_string(), json!([["a", 1]]));
+        assert!(schema.validate(&doc).is_err());
+        doc.insert("tags".to_string(), json!([]));
+        doc.insert("address".to_string(), json!({"zip": 1}));
+        assert!(schema.validate(&doc).is_err());
+    }
+
+    #[test]
+    fn unknown_wire_type_names_table_and_field() {
+        let wire: WireCollectionSchema = serde_json::from_value(json!({
+            "profile": {
+                "required": true,
+                "type": "object",
+                "fields": {"age": {"required": false, "type": "integer"}}
+            }
+        }))
+        .expect("wire should deserialize");
+
+        let error = Schema::from_wire_table("users", &wire).expect_err("type should be rejected");
+        assert!(error
+            .to_string()
+            .contains("unsupported schema type 'integer' for users.profile.age"));
+    }
+
+    #[test]
+    fn id_fields_require_table_prefix_and_nonempty_rest() {
+        let mut fields = BTreeMap::new();
+        fields.insert(
+            "owner".to_string(),
+            SchemaField {
+                required: true,
+                field_type: SchemaType::Id("users".to_string()),
+            },
+        );
+        let schema = Schema::with_fields(fields);
+        let check = |owner: serde_json::Value| {
+            let mut doc = BTreeMap::new();
+            doc.insert("owner".to_string(), owner);
+            schema.validate(&doc).is_ok()
+        };
+
+        assert!(check(json!("users:1")));
+        assert!(check(json!("users:0190a1b2-c3d4")));
+        assert!(!check(json!("users:")));
+        assert!(!check(json!("user:1")));
+        assert!(!check(json!("users1")));
+        assert!(!check(json!("admins:1")));
+        assert!(!check(json!("users")));
+        assert!(!check(json!(1)));
+    }
+
+    #[test]
+    fn id_fields_need_a_clean_suffix_after_the_table_prefix() {
+        assert!(is_id_for_table("users:42", "users"));
+        assert!(is_id_for_table("users:0190-ab", "users"));
+        for id in [
+            "users",
+            "users:",
+            "users:a:b",
+            "users:a b",
+            "users:\tb",
+            "users:a\u{7}",
+            "usersx:1",
+            "posts:1",
+        ] {
+            assert!(!is_id_for_table(id, "users"), "{id:?} should be rejected");
+        }
+    }
 }
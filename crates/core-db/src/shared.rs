@@ -0,0 +1,39 @@
+use crate::engine::InMemoryEngine;
+use crate::error::CoreResult;
+use crate::types::{Document, WriteOperation};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[derive(Debug, Clone, Default)]
+pub struct SharedEngine {
+    inner: Arc<RwLock<InMemoryEngine>>,
+}
+
+impl SharedEngine {
+    pub fn new(engine: InMemoryEngine) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(engine)),
+        }
+    }
+
+    // write_batch only swaps in a table after the whole batch succeeds, so a
+    // writer that panicked cannot leave a half-applied batch behind.
+    pub fn read(&self) -> RwLockReadGuard<'_, InMemoryEngine> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, InMemoryEngine> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn get(&self, table: &str, id: &str) -> CoreResult<Document> {
+        self.read().get(table, id)
+    }
+
+    pub fn list_documents(&self, table: &str) -> CoreResult<Vec<Document>> {
+        self.read().list_documents(table)
+    }
+
+    pub fn write_batch(&self, table: &str, ops: &[WriteOperation]) -> CoreResult<Vec<Document>> {
+        self.write().write_batch(table, ops)
+    }
+}
@@ -0,0 +1,81 @@
+use crate::types::{Document, Quota, TableName, Value};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct OperationCounts {
+    pub reads: u64,
+    pub puts: u64,
+    pub patches: u64,
+    pub deletes: u64,
+    pub batches: u64,
+    pub failed_batches: u64,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct TableStats {
+    pub name: TableName,
+    pub document_count: usize,
+    pub estimated_bytes: usize,
+    pub has_schema: bool,
+    pub quota: Option<Quota>,
+    pub index_count: usize,
+    pub indexes: Vec<IndexStats>,
+}
+
+/// Size of one index. A multikey index holds one entry per array element,
+/// so `entry_count` can exceed the table's document count.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct IndexStats {
+    pub name: String,
+    pub entry_count: usize,
+    /// Key, sort key and id bytes, estimated like document sizes.
+    pub estimated_bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct EngineStats {
+    pub tables: Vec<TableStats>,
+    pub total_documents: usize,
+    pub total_estimated_bytes: usize,
+    pub current_revision: u64,
+    pub operations: OperationCounts,
+}
+
+pub fn estimated_document_size(document: &Document) -> usize {
+    document.id.len() + std::mem::size_of::<u64>() + estimated_fields_size(&document.fields)
+}
+
+pub fn estimated_fields_size(fields: &BTreeMap<String, Value>) -> usize {
+    fields
+        .iter()
+        .map(|(key, value)| key.len() + estimated_value_size(value))
+        .sum()
+}
+
+pub fn estimated_value_size(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Bool(_) => 1,
+        Value::Number(_) => 8,
+        Value::String(text) => text.len(),
+        Value::Array(items) => items.iter().map(estimated_value_size).sum(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| key.len() + estimated_value_size(value))
+            .sum(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::estimated_value_size;
+    use serde_json::json;
+
+    #[test]
+    fn estimates_nested_values() {
+        assert_eq!(estimated_value_size(&json!(null)), 1);
+        assert_eq!(estimated_value_size(&json!("abcd")), 4);
+        assert_eq!(estimated_value_size(&json!([1, true])), 9);
+        assert_eq!(estimated_value_size(&json!({"ab": "cd"})), 4);
+    }
+}
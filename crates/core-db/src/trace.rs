@@ -0,0 +1,72 @@
+//! Optional `tracing` spans around engine operations. Without the `tracing`
+//! feature, `OpSpan` is zero-sized and every function here is an empty stub.
+
+use crate::error::CoreResult;
+use crate::metrics::OpKind;
+#[cfg(feature = "tracing")]
+use crate::metrics::Outcome;
+use std::time::Duration;
+
+/// A span covering one metered operation. It is entered on creation and
+/// closed by `finish`, which records how the operation ended.
+pub(crate) struct OpSpan {
+    #[cfg(feature = "tracing")]
+    span: tracing::span::EnteredSpan,
+}
+
+#[cfg(feature = "tracing")]
+impl OpSpan {
+    pub(crate) fn enter(op: OpKind, table: &str) -> Self {
+        let span = tracing::info_span!(
+            "core_db",
+            op = ?op,
+            table,
+            index = tracing::field::Empty,
+            documents = tracing::field::Empty,
+            elapsed_us = tracing::field::Empty,
+            outcome = tracing::field::Empty,
+        );
+        Self {
+            span: span.entered(),
+        }
+    }
+
+    pub(crate) fn record_index(&self, index: &str) {
+        self.span.record("index", index);
+    }
+
+    pub(crate) fn record_documents(&self, count: usize) {
+        self.span.record("documents", count);
+    }
+
+    pub(crate) fn finish<T>(self, result: &CoreResult<T>, elapsed: Duration) {
+        let outcome = Outcome::of(result);
+        let elapsed_us = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        self.span.record("elapsed_us", elapsed_us);
+        self.span.record("outcome", tracing::field::debug(outcome));
+        if let Err(error) = result {
+            match outcome {
+                Outcome::SchemaViolation => tracing::warn!(%error, "schema violation"),
+                Outcome::Conflict => tracing::warn!(%error, "revision conflict"),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl OpSpan {
+    #[inline(always)]
+    pub(crate) fn enter(_op: OpKind, _table: &str) -> Self {
+        Self {}
+    }
+
+    #[inline(always)]
+    pub(crate) fn record_index(&self, _index: &str) {}
+
+    #[inline(always)]
+    pub(crate) fn record_documents(&self, _count: usize) {}
+
+    #[inline(always)]
+    pub(crate) fn finish<T>(self, _result: &CoreResult<T>, _elapsed: Duration) {}
+}
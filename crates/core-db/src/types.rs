@@ -1,3 +1,7 @@
+use crate::error::{CoreError, CoreResult};
+use crate::index::IndexDefinition;
+use crate::schema::WireCollectionSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -5,9 +9,15 @@ pub type TableName = String;
 pub type Value = serde_json::Value;
 pub type DocumentId = String;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct Revision(pub u64);
 
+impl std::fmt::Display for Revision {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}", self.0)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Document {
     pub id: DocumentId,
@@ -15,20 +25,219 @@ pub struct Document {
     pub fields: BTreeMap<String, Value>,
 }
 
+pub const ID_FIELD: &str = "_id";
+pub const REVISION_FIELD: &str = "_revision";
+/// System table that receives one record per changed document while audit
+/// logging is enabled.
+pub const AUDIT_TABLE: &str = "_audit";
+
+impl Document {
+    pub fn to_value(&self) -> Value {
+        let mut object = serde_json::Map::new();
+        object.insert(ID_FIELD.to_string(), Value::String(self.id.clone()));
+        object.insert(REVISION_FIELD.to_string(), Value::from(self.revision.0));
+        for (key, value) in &self.fields {
+            object.insert(key.clone(), value.clone());
+        }
+        Value::Object(object)
+    }
+
+    /// The user fields as a JSON object, without `_id` or `_revision`.
+    pub fn user_fields_json(&self) -> Value {
+        Value::Object(
+            self.fields
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn deserialize_fields<T: DeserializeOwned>(&self) -> CoreResult<T> {
+        let object = Value::Object(self.fields.clone().into_iter().collect());
+        serde_json::from_value(object).map_err(|error| {
+            CoreError::InvalidOperation(format!(
+                "document {} cannot be deserialized: {}",
+                self.id, error
+            ))
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct NewDocument {
     pub id: Option<DocumentId>,
     pub fields: BTreeMap<String, Value>,
 }
 
+impl NewDocument {
+    pub fn from_serializable<T: Serialize>(id: Option<DocumentId>, value: &T) -> CoreResult<Self> {
+        let serialized = serde_json::to_value(value).map_err(|error| {
+            CoreError::InvalidOperation(format!("value cannot be serialized: {}", error))
+        })?;
+
+        match serialized {
+            Value::Object(map) => Ok(Self {
+                id,
+                fields: map.into_iter().collect(),
+            }),
+            other => Err(CoreError::InvalidOperation(format!(
+                "typed documents must serialize to an object, got {}",
+                other
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Quota {
+    pub max_documents: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TtlPolicy {
+    pub field: String,
+    pub ttl_ms: u64,
+}
+
+impl TtlPolicy {
+    pub fn is_expired(&self, document: &Document, now_ms: f64) -> bool {
+        document
+            .fields
+            .get(&self.field)
+            .and_then(Value::as_f64)
+            .is_some_and(|stamp| stamp + self.ttl_ms as f64 <= now_ms)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CopyTableOptions {
+    pub preserve_ids: bool,
+    pub copy_schema: bool,
+    pub merge: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HealthState {
+    #[default]
+    Healthy,
+    Degraded(String),
+    ReadOnly(String),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct TableState {
     pub name: TableName,
     pub document_count: usize,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TableDiff {
+    pub name: TableName,
+    pub added: Vec<DocumentId>,
+    pub removed: Vec<DocumentId>,
+    pub changed: Vec<DocumentId>,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TableSnapshot {
+    pub name: TableName,
+    pub schema: WireCollectionSchema,
+    pub quota: Option<Quota>,
+    pub ttl: Option<TtlPolicy>,
+    #[serde(default)]
+    pub indexes: Vec<IndexDefinition>,
+    pub documents: Vec<Document>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Backup {
+    pub revision: u64,
+    pub tables: Vec<TableSnapshot>,
+}
+
+impl Backup {
+    pub fn to_bytes(&self) -> CoreResult<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|error| {
+            CoreError::InvalidOperation(format!("backup cannot be encoded: {}", error))
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> CoreResult<Self> {
+        serde_json::from_slice(bytes).map_err(|error| {
+            CoreError::InvalidOperation(format!("backup cannot be decoded: {}", error))
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum WriteOperation {
     Put(NewDocument),
+    Patch {
+        id: DocumentId,
+        fields: BTreeMap<String, Value>,
+    },
     Delete(DocumentId),
+    /// Adds `delta` to an integer field, starting from zero when it is absent.
+    Increment {
+        id: DocumentId,
+        field: String,
+        delta: i64,
+    },
+    /// Appends `value` to an array field unless an equal element is already
+    /// there. An absent field becomes a one-element array.
+    ArrayPush {
+        id: DocumentId,
+        field: String,
+        value: Value,
+    },
+    /// Removes every element equal to `value` from an array field.
+    ArrayRemove {
+        id: DocumentId,
+        field: String,
+        value: Value,
+    },
+    PutIf {
+        document: NewDocument,
+        expected: Revision,
+    },
+    DeleteIf {
+        id: DocumentId,
+        expected: Revision,
+    },
+}
+
+impl WriteOperation {
+    /// A short name for the operation, used in error context.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Put(_) => "put",
+            Self::Patch { .. } => "patch",
+            Self::Delete(_) => "delete",
+            Self::Increment { .. } => "increment",
+            Self::ArrayPush { .. } => "array_push",
+            Self::ArrayRemove { .. } => "array_remove",
+            Self::PutIf { .. } => "put_if",
+            Self::DeleteIf { .. } => "delete_if",
+        }
+    }
+
+    /// The document the operation targets, when the caller named one.
+    pub fn document_id(&self) -> Option<&str> {
+        match self {
+            Self::Put(document) | Self::PutIf { document, .. } => document.id.as_deref(),
+            Self::Patch { id, .. }
+            | Self::Delete(id)
+            | Self::Increment { id, .. }
+            | Self::ArrayPush { id, .. }
+            | Self::ArrayRemove { id, .. }
+            | Self::DeleteIf { id, .. } => Some(id),
+        }
+    }
 }
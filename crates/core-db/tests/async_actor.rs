@@ -0,0 +1,114 @@
+#![cfg(feature = "async")]
+
+use core_db::{
+    CoreError, EngineActor, InMemoryEngine, NewDocument, Schema, TtlPolicy, WriteOperation,
+};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod common;
+use common::{put_user, users_schema};
+
+#[tokio::test]
+async fn handle_inserts_and_reads_back_through_the_actor() {
+    let handle = EngineActor::spawn(InMemoryEngine::new());
+    handle
+        .create_table("users", users_schema())
+        .await
+        .expect("table should be created");
+
+    let tasks: Vec<_> = (0..8)
+        .map(|index| {
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                handle
+                    .write_batch("users", vec![put_user(&format!("u_{index}"), "async")])
+                    .await
+            })
+        })
+        .collect();
+    for task in tasks {
+        task.await
+            .expect("task should finish")
+            .expect("write should work");
+    }
+
+    let fetched = handle.get("users", "u_3").await.expect("doc must exist");
+    assert_eq!(fetched.fields.get("name"), Some(&"async".into()));
+    assert_eq!(
+        handle
+            .list_documents("users")
+            .await
+            .expect("list should work")
+            .len(),
+        8
+    );
+    assert!(matches!(
+        handle.get("missing", "u_1").await,
+        Err(CoreError::TableNotFound { .. })
+    ));
+}
+
+#[tokio::test]
+async fn spawned_actor_sweeps_expired_documents_on_an_interval() {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock is after the epoch")
+        .as_millis() as f64;
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("sessions", Schema::default())
+        .expect("table should be created");
+    engine
+        .set_ttl(
+            "sessions",
+            Some(TtlPolicy {
+                field: "created_at".to_string(),
+                ttl_ms: 60_000,
+            }),
+        )
+        .expect("ttl should be set");
+    let session = |id: &str, created_at: f64| {
+        WriteOperation::Put(NewDocument {
+            id: Some(id.to_string()),
+            fields: BTreeMap::from([("created_at".to_string(), created_at.into())]),
+        })
+    };
+    engine
+        .write_batch(
+            "sessions",
+            &[
+                session("old", now_ms - 120_000.0),
+                session("fresh", now_ms + 3_600_000.0),
+            ],
+        )
+        .expect("insert should work");
+
+    let handle = EngineActor::spawn_with_expiration(engine, Duration::from_millis(5));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let remaining = loop {
+        let remaining = handle
+            .list_documents("sessions")
+            .await
+            .expect("list should work");
+        if remaining.len() == 1 || Instant::now() >= deadline {
+            break remaining;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    };
+    assert_eq!(
+        remaining.len(),
+        1,
+        "the sweep should run before the deadline"
+    );
+    assert_eq!(remaining[0].id, "fresh");
+
+    let later = now_ms + 3_600_000.0 + 60_000.0;
+    assert_eq!(
+        handle
+            .run_expiration(later)
+            .await
+            .expect("sweep should work"),
+        1
+    );
+}
@@ -0,0 +1,50 @@
+// Each integration test binary compiles its own copy and uses a subset.
+#![allow(dead_code)]
+
+use core_db::{InMemoryEngine, NewDocument, Schema, SchemaField, SchemaType, WriteOperation};
+use std::collections::BTreeMap;
+
+pub fn users_schema() -> Schema {
+    let mut fields = BTreeMap::new();
+    fields.insert(
+        "name".to_string(),
+        SchemaField {
+            required: true,
+            field_type: SchemaType::String,
+        },
+    );
+    Schema::with_fields(fields)
+}
+
+pub fn put_user(id: &str, name: &str) -> WriteOperation {
+    put_named(Some(id), name)
+}
+
+/// A user put that leaves the id to the engine's generator.
+pub fn put_unnamed(name: &str) -> WriteOperation {
+    put_named(None, name)
+}
+
+fn put_named(id: Option<&str>, name: &str) -> WriteOperation {
+    let mut fields = BTreeMap::new();
+    fields.insert("name".to_string(), serde_json::json!(name));
+    WriteOperation::Put(NewDocument {
+        id: id.map(str::to_string),
+        fields,
+    })
+}
+
+/// Ids matching an exact key on `index`, in index order.
+pub fn query_ids(
+    engine: &InMemoryEngine,
+    table: &str,
+    index: &str,
+    values: &[serde_json::Value],
+) -> Vec<String> {
+    engine
+        .query_index(table, index, values)
+        .expect("query should succeed")
+        .into_iter()
+        .map(|document| document.id)
+        .collect()
+}
@@ -1,17 +1,17 @@
-use core_db::{InMemoryEngine, NewDocument, Schema, SchemaField, SchemaType, WriteOperation};
+use core_db::{
+    Backup, CopyTableOptions, CoreError, EngineSet, HealthState, InMemoryEngine, InMemoryMetrics,
+    IndexDefinition, NamespaceTemplate, NewDocument, OpKind, Outcome, Quota, RangeOptions, Schema,
+    SchemaField, SchemaType, SequentialIds, SharedEngine, TableSnapshot, TtlPolicy,
+    WireCollectionSchema, WireDatabaseSchema, WireSchemaField, WireSchemaType, WriteOperation,
+    AUDIT_TABLE,
+};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::sync::Arc;
 
-fn users_schema() -> Schema {
-    let mut fields = BTreeMap::new();
-    fields.insert(
-        "name".to_string(),
-        SchemaField {
-            required: true,
-            field_type: SchemaType::String,
-        },
-    );
-    Schema::with_fields(fields)
-}
+mod common;
+use common::{put_unnamed, put_user, query_ids, users_schema};
 
 #[test]
 fn list_tables_and_delete_document() {
@@ -20,31 +20,2272 @@ fn list_tables_and_delete_document() {
         .create_table("users", users_schema())
         .expect("table should be created");
 
+    let written = engine
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("insert should work");
+    assert_eq!(written.len(), 1);
+    let fetched = engine.get("users", "u_1").expect("doc must exist");
+    assert_eq!(fetched.revision.0, 1);
+
+    let tables = engine.list_tables();
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].name, "users");
+    assert_eq!(tables[0].document_count, 1);
+
+    engine
+        .write_batch("users", &[WriteOperation::Delete("u_1".to_string())])
+        .expect("delete should work");
+
+    let users = engine.list_documents("users").expect("list should succeed");
+    assert!(users.is_empty());
+}
+
+#[test]
+fn count_where_matches_filtered_listing() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+
+    let ops: Vec<WriteOperation> = ["Ada", "Lin", "Ada", "Grace"]
+        .iter()
+        .map(|name| {
+            let mut fields = BTreeMap::new();
+            fields.insert(
+                "name".to_string(),
+                serde_json::Value::String(name.to_string()),
+            );
+            WriteOperation::Put(NewDocument { id: None, fields })
+        })
+        .collect();
+    engine
+        .write_batch("users", &ops)
+        .expect("insert should work");
+
+    let is_ada = |doc: &core_db::Document| doc.fields.get("name") == Some(&"Ada".into());
+    let expected = engine
+        .list_documents("users")
+        .expect("list should succeed")
+        .iter()
+        .filter(|doc| is_ada(doc))
+        .count();
+
+    let counted = engine
+        .count_where("users", is_ada)
+        .expect("count should succeed");
+    assert_eq!(counted, expected);
+    assert_eq!(counted, 2);
+    assert!(engine.count_where("missing", is_ada).is_err());
+}
+
+#[test]
+fn stats_track_operation_sequence() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .create_table("logs", Schema::default())
+        .expect("table should be created");
+    engine
+        .create_index("users", "by_name", &["name"])
+        .expect("index should be created");
+
     let mut user = BTreeMap::new();
     user.insert(
         "name".to_string(),
         serde_json::Value::String("Lin".to_string()),
     );
-
     engine
         .write_batch(
             "users",
-            &[WriteOperation::Put(NewDocument {
-                id: Some("u_1".to_string()),
-                fields: user,
-            })],
+            &[
+                WriteOperation::Put(NewDocument {
+                    id: Some("u_1".to_string()),
+                    fields: user.clone(),
+                }),
+                WriteOperation::Put(NewDocument {
+                    id: Some("u_2".to_string()),
+                    fields: user,
+                }),
+            ],
         )
         .expect("insert should work");
+    let after_insert = engine.table_stats("users").expect("stats should exist");
+    assert_eq!(after_insert.document_count, 2);
+    assert!(after_insert.has_schema);
+    assert!(after_insert.estimated_bytes > 0);
+    assert_eq!(after_insert.index_count, 1);
+    assert_eq!(after_insert.indexes[0].name, "by_name");
+    assert_eq!(after_insert.indexes[0].entry_count, 2);
+    // One "Lin" key plus the ids "u_1" and "u_2".
+    assert_eq!(after_insert.indexes[0].estimated_bytes, 9);
+
+    engine
+        .write_batch("users", &[WriteOperation::Delete("u_1".to_string())])
+        .expect("delete should work");
+    assert!(engine
+        .write_batch("users", &[WriteOperation::Delete("u_1".to_string())])
+        .is_err());
+    engine.get("users", "u_2").expect("doc must exist");
+
+    let stats = engine.stats();
+    assert_eq!(stats.tables.len(), 2);
+    assert_eq!(stats.total_documents, 1);
+    assert_eq!(
+        stats.total_estimated_bytes,
+        after_insert.estimated_bytes / 2
+    );
+    assert!(!stats.tables[0].has_schema);
+    assert_eq!(stats.tables[1].indexes[0].entry_count, 1);
+    assert_eq!(stats.tables[1].indexes[0].estimated_bytes, 6);
+    assert_eq!(stats.operations.puts, 2);
+    assert_eq!(stats.operations.deletes, 1);
+    assert_eq!(stats.operations.batches, 3);
+    assert_eq!(stats.operations.failed_batches, 1);
+    assert_eq!(stats.operations.reads, 1);
+    assert!(serde_json::to_string(&stats).is_ok());
+}
+
+#[test]
+fn optional_lookups_distinguish_missing_table_and_document() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("insert should work");
+
+    let found = engine.get_opt("users", "u_1").expect("table exists");
+    assert_eq!(found.map(|doc| doc.id), Some("u_1".to_string()));
+    assert_eq!(engine.get_opt("users", "u_2").expect("table exists"), None);
+    assert!(engine.contains("users", "u_1").expect("table exists"));
+    assert!(!engine.contains("users", "u_2").expect("table exists"));
+
+    assert!(matches!(
+        engine.get_opt("missing", "u_1"),
+        Err(CoreError::TableNotFound { .. })
+    ));
+    assert!(matches!(
+        engine.contains("missing", "u_1"),
+        Err(CoreError::TableNotFound { .. })
+    ));
+}
+
+#[test]
+fn snapshot_is_independent_and_diffable() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin"), put_user("u_2", "Ada")])
+        .expect("insert should work");
+
+    let mut fork = engine.snapshot();
+    fork.write_batch(
+        "users",
+        &[
+            WriteOperation::Delete("u_1".to_string()),
+            put_user("u_2", "Grace"),
+            put_user("u_3", "Edsger"),
+        ],
+    )
+    .expect("fork write should work");
+    fork.create_table("logs", Schema::default())
+        .expect("fork table should be created");
+
+    assert_eq!(engine.list_documents("users").expect("list").len(), 2);
+    assert!(engine.get("users", "u_3").is_err());
+    assert!(engine.list_documents("logs").is_err());
+
+    let diff = engine.diff(&fork);
+    assert_eq!(diff.len(), 1);
+    assert_eq!(diff[0].name, "users");
+    assert_eq!(diff[0].added, vec!["u_3".to_string()]);
+    assert_eq!(diff[0].removed, vec!["u_1".to_string()]);
+    assert_eq!(diff[0].changed, vec!["u_2".to_string()]);
+    assert!(engine.diff(&engine.snapshot()).is_empty());
+}
 
+#[test]
+fn auto_create_tables_on_write_when_enabled() {
+    let mut engine = InMemoryEngine::new();
+    assert!(matches!(
+        engine.write_batch("users", &[put_user("u_1", "Lin")]),
+        Err(CoreError::TableNotFound { .. })
+    ));
+
+    engine.set_auto_create_tables(true);
+    assert!(engine
+        .write_batch("users", &[WriteOperation::Delete("u_1".to_string())])
+        .is_err());
+    assert!(engine.list_tables().is_empty());
+
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("write should create the table");
     let tables = engine.list_tables();
     assert_eq!(tables.len(), 1);
-    assert_eq!(tables[0].name, "users");
     assert_eq!(tables[0].document_count, 1);
+}
+
+#[test]
+fn list_documents_limited_clamps_offset_and_limit() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch(
+            "users",
+            &[
+                put_user("u_3", "Lin"),
+                put_user("u_1", "Ada"),
+                put_user("u_4", "Grace"),
+                put_user("u_2", "Edsger"),
+            ],
+        )
+        .expect("insert should work");
+
+    let ids = |docs: Vec<core_db::Document>| docs.into_iter().map(|doc| doc.id).collect::<Vec<_>>();
+
+    let middle = engine
+        .list_documents_limited("users", 1, 2)
+        .expect("list should succeed");
+    assert_eq!(ids(middle), vec!["u_2", "u_3"]);
+
+    let past_end = engine
+        .list_documents_limited("users", 10, 2)
+        .expect("list should succeed");
+    assert!(past_end.is_empty());
+
+    let remaining = engine
+        .list_documents_limited("users", 2, 100)
+        .expect("list should succeed");
+    assert_eq!(ids(remaining), vec!["u_3", "u_4"]);
+}
+
+#[test]
+fn table_quota_limits_growth_and_deletes_free_space() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let quota = Quota {
+        max_documents: Some(2),
+        max_bytes: None,
+    };
+    engine
+        .set_table_quota("users", Some(quota))
+        .expect("quota should be set");
+
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin"), put_user("u_2", "Ada")])
+        .expect("insert within quota should work");
+    let over = engine.write_batch("users", &[put_user("u_3", "Grace")]);
+    assert!(matches!(over, Err(CoreError::QuotaExceeded(_))));
+    assert_eq!(engine.list_documents("users").expect("list").len(), 2);
+
+    engine
+        .write_batch(
+            "users",
+            &[
+                WriteOperation::Delete("u_1".to_string()),
+                put_user("u_3", "Grace"),
+            ],
+        )
+        .expect("delete should free quota within the batch");
+
+    let byte_limit = engine.table_stats("users").expect("stats").estimated_bytes;
+    engine
+        .set_table_quota(
+            "users",
+            Some(Quota {
+                max_documents: None,
+                max_bytes: Some(byte_limit),
+            }),
+        )
+        .expect("quota should be set");
+    assert!(matches!(
+        engine.write_batch("users", &[put_user("u_3", "Grace Hopper")]),
+        Err(CoreError::QuotaExceeded(_))
+    ));
+    engine
+        .write_batch("users", &[put_user("u_3", "G")])
+        .expect("shrinking a document should be allowed");
+
+    let stats = engine.table_stats("users").expect("stats");
+    assert_eq!(
+        stats.quota.and_then(|quota| quota.max_bytes),
+        Some(byte_limit)
+    );
+}
+
+#[test]
+fn find_array_contains_scans_overlapping_tags() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("posts", Schema::default())
+        .expect("table should be created");
+
+    let post = |id: &str, tags: serde_json::Value| {
+        let mut fields = BTreeMap::new();
+        fields.insert("tags".to_string(), tags);
+        WriteOperation::Put(NewDocument {
+            id: Some(id.to_string()),
+            fields,
+        })
+    };
+    engine
+        .write_batch(
+            "posts",
+            &[
+                post("p_1", serde_json::json!(["rust", "db"])),
+                post("p_2", serde_json::json!(["db"])),
+                post("p_3", serde_json::json!(["rust", "web"])),
+                post("p_4", serde_json::json!("rust")),
+            ],
+        )
+        .expect("insert should work");
+
+    let rust = engine
+        .find_array_contains("posts", "tags", &"rust".into())
+        .expect("scan should succeed");
+    let ids: Vec<_> = rust.iter().map(|doc| doc.id.as_str()).collect();
+    assert_eq!(ids, vec!["p_1", "p_3"]);
+
+    let db = engine
+        .find_array_contains("posts", "tags", &"db".into())
+        .expect("scan should succeed");
+    assert_eq!(db.len(), 2);
+    assert!(engine
+        .find_array_contains("posts", "tags", &"go".into())
+        .expect("scan should succeed")
+        .is_empty());
+}
+
+#[test]
+fn export_and_import_table_round_trip() {
+    let mut source = InMemoryEngine::new();
+    source
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    source
+        .write_batch("users", &[put_user("u_1", "Lin"), put_user("u_2", "Ada")])
+        .expect("insert should work");
+
+    let snapshot = source.export_table("users").expect("export should work");
+    let encoded = serde_json::to_string(&snapshot).expect("snapshot should serialize");
+    let decoded: TableSnapshot = serde_json::from_str(&encoded).expect("snapshot should parse");
+
+    let mut target = InMemoryEngine::new();
+    target
+        .import_table(decoded.clone())
+        .expect("import should work");
+    assert_eq!(
+        target.list_documents("users").expect("list"),
+        source.list_documents("users").expect("list")
+    );
+    assert!(target.import_table(decoded).is_err());
+
+    let written = target
+        .write_batch("users", &[put_user("u_3", "Grace")])
+        .expect("insert after import should work");
+    assert!(written[0].revision.0 > 2);
+}
+
+#[test]
+fn expiration_sweeps_in_bounded_batches() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("sessions", Schema::default())
+        .expect("table should be created");
+    engine
+        .set_ttl(
+            "sessions",
+            Some(TtlPolicy {
+                field: "createdAt".to_string(),
+                ttl_ms: 100,
+            }),
+        )
+        .expect("ttl should be set");
+    engine.set_expiration_batch_limit(2);
+
+    let session = |id: &str, created_at: serde_json::Value| {
+        let mut fields = BTreeMap::new();
+        fields.insert("createdAt".to_string(), created_at);
+        WriteOperation::Put(NewDocument {
+            id: Some(id.to_string()),
+            fields,
+        })
+    };
+    engine
+        .write_batch(
+            "sessions",
+            &[
+                session("s_1", serde_json::json!(0)),
+                session("s_2", serde_json::json!(10)),
+                session("s_3", serde_json::json!(20)),
+                session("s_4", serde_json::json!(500)),
+                session("s_5", serde_json::json!("yesterday")),
+            ],
+        )
+        .expect("insert should work");
+    let mut no_stamp = BTreeMap::new();
+    no_stamp.insert("user".to_string(), serde_json::json!("lin"));
+    engine
+        .write_batch(
+            "sessions",
+            &[WriteOperation::Put(NewDocument {
+                id: Some("s_6".to_string()),
+                fields: no_stamp,
+            })],
+        )
+        .expect("insert should work");
+
+    assert_eq!(engine.run_expiration(200.0).expect("sweep"), 2);
+    assert_eq!(engine.run_expiration(200.0).expect("sweep"), 1);
+    assert_eq!(engine.run_expiration(200.0).expect("sweep"), 0);
+
+    let left: Vec<_> = engine
+        .list_documents("sessions")
+        .expect("list")
+        .into_iter()
+        .map(|doc| doc.id)
+        .collect();
+    assert_eq!(left, vec!["s_4", "s_5", "s_6"]);
+    assert_eq!(engine.stats().operations.deletes, 3);
+}
+
+#[test]
+fn backup_and_restore_replace_state_atomically() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin"), put_user("u_2", "Ada")])
+        .expect("insert should work");
+
+    let bytes = engine
+        .backup()
+        .expect("backup should work")
+        .to_bytes()
+        .expect("backup should encode");
+    let expected = engine.list_documents("users").expect("list");
 
     engine
         .write_batch("users", &[WriteOperation::Delete("u_1".to_string())])
         .expect("delete should work");
+    engine
+        .create_table("logs", Schema::default())
+        .expect("table should be created");
 
-    let users = engine.list_documents("users").expect("list should succeed");
-    assert!(users.is_empty());
+    let mut corrupt = Backup::from_bytes(&bytes).expect("backup should decode");
+    corrupt.tables[0].documents[0].fields.clear();
+    assert!(engine.restore(corrupt).is_err());
+    assert_eq!(engine.list_tables().len(), 2);
+
+    engine
+        .restore(Backup::from_bytes(&bytes).expect("backup should decode"))
+        .expect("restore should work");
+    assert_eq!(engine.list_documents("users").expect("list"), expected);
+    assert!(engine.list_documents("logs").is_err());
+
+    let written = engine
+        .write_batch("users", &[put_user("u_3", "Grace")])
+        .expect("insert after restore should work");
+    assert_eq!(written[0].revision.0, 3);
+}
+
+#[test]
+fn namespaces_are_isolated_and_share_a_template() {
+    let mut schema = WireDatabaseSchema::new();
+    schema.insert(
+        "users".to_string(),
+        WireCollectionSchema::from([(
+            "name".to_string(),
+            WireSchemaField {
+                required: true,
+                field_type: WireSchemaType::from("string"),
+            },
+        )]),
+    );
+    let mut template = NamespaceTemplate::from(schema);
+    template.indexes.insert(
+        "users".to_string(),
+        vec![IndexDefinition::new("by_name", &["name"])],
+    );
+
+    let mut set = EngineSet::new();
+    set.create_namespace("acme", &template)
+        .expect("namespace should be created")
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("insert should work");
+    set.create_namespace("globex", &template)
+        .expect("namespace should be created");
+    assert!(matches!(
+        set.create_namespace("acme", &template),
+        Err(CoreError::NamespaceAlreadyExists(_))
+    ));
+
+    assert_eq!(
+        set.namespace("acme")
+            .expect("namespace exists")
+            .query_index("users", "by_name", &[serde_json::json!("Lin")])
+            .expect("template index should exist")
+            .len(),
+        1
+    );
+
+    let globex = set.namespace_mut("globex").expect("namespace exists");
+    assert!(globex.get("users", "u_1").is_err());
+    assert!(globex
+        .write_batch(
+            "users",
+            &[WriteOperation::Put(NewDocument {
+                id: None,
+                fields: BTreeMap::new(),
+            })]
+        )
+        .is_err());
+
+    let states = set.list_namespaces();
+    assert_eq!(states.len(), 2);
+    assert_eq!(states[0].name, "acme");
+    assert_eq!(states[0].stats.total_documents, 1);
+    assert_eq!(states[1].stats.total_documents, 0);
+
+    set.drop_namespace("acme").expect("drop should work");
+    assert!(matches!(
+        set.namespace("acme"),
+        Err(CoreError::NamespaceNotFound(_))
+    ));
+}
+
+#[test]
+fn shared_engine_serves_concurrent_readers_and_a_writer() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let shared = SharedEngine::new(engine);
+    shared
+        .write_batch("users", &[put_user("u_0", "seed")])
+        .expect("seed insert should work");
+
+    let writer = {
+        let shared = shared.clone();
+        std::thread::spawn(move || {
+            for index in 1..=50 {
+                shared
+                    .write_batch("users", &[put_user(&format!("u_{index}"), "writer")])
+                    .expect("insert should work");
+            }
+        })
+    };
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                for _ in 0..50 {
+                    shared.get("users", "u_0").expect("seed doc must exist");
+                    let count = shared.list_documents("users").expect("list").len();
+                    assert!((1..=51).contains(&count));
+                }
+            })
+        })
+        .collect();
+
+    writer.join().expect("writer should finish");
+    for reader in readers {
+        reader.join().expect("reader should finish");
+    }
+    assert_eq!(
+        shared.read().list_documents("users").expect("list").len(),
+        51
+    );
+    assert_eq!(shared.write().list_tables()[0].document_count, 51);
+}
+
+#[test]
+fn copy_table_mints_or_preserves_ids_in_one_batch() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin"), put_user("u_2", "Ada")])
+        .expect("insert should work");
+    let batches_before = engine.stats().operations.batches;
+
+    let preserved = CopyTableOptions {
+        preserve_ids: true,
+        copy_schema: true,
+        merge: false,
+    };
+    assert_eq!(
+        engine
+            .copy_table("users", "users_copy", preserved)
+            .expect("copy should work"),
+        2
+    );
+    assert_eq!(engine.stats().operations.batches, batches_before + 1);
+    assert_eq!(
+        engine
+            .get("users_copy", "u_1")
+            .expect("doc must exist")
+            .fields,
+        engine.get("users", "u_1").expect("doc must exist").fields
+    );
+    assert!(engine.table_stats("users_copy").expect("stats").has_schema);
+
+    assert!(engine.copy_table("users", "users_copy", preserved).is_err());
+    engine
+        .copy_table(
+            "users",
+            "users_copy",
+            CopyTableOptions {
+                merge: true,
+                ..CopyTableOptions::default()
+            },
+        )
+        .expect("merge copy should work");
+    let merged = engine.list_documents("users_copy").expect("list");
+    assert_eq!(merged.len(), 4);
+    assert_eq!(
+        merged.iter().filter(|doc| doc.id.starts_with("u_")).count(),
+        2
+    );
+}
+
+#[test]
+fn metrics_sink_counts_operations_by_outcome() {
+    let metrics = Arc::new(InMemoryMetrics::new());
+    let mut engine = InMemoryEngine::new();
+    engine.set_metrics(metrics.clone());
+
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("insert should work");
+    let mut invalid = BTreeMap::new();
+    invalid.insert("name".to_string(), serde_json::Value::Bool(true));
+    assert!(engine
+        .write_batch(
+            "users",
+            &[WriteOperation::Put(NewDocument {
+                id: None,
+                fields: invalid,
+            })],
+        )
+        .is_err());
+    engine.get("users", "u_1").expect("doc must exist");
+    assert!(engine.get("users", "u_2").is_err());
+    engine.list_documents("users").expect("list should work");
+
+    assert_eq!(metrics.count(OpKind::CreateTable, Outcome::Success), 1);
+    assert_eq!(metrics.count(OpKind::WriteBatch, Outcome::Success), 1);
+    assert_eq!(
+        metrics.count(OpKind::WriteBatch, Outcome::SchemaViolation),
+        1
+    );
+    assert_eq!(metrics.count(OpKind::Get, Outcome::Success), 1);
+    assert_eq!(metrics.count(OpKind::Get, Outcome::NotFound), 1);
+    assert_eq!(
+        metrics
+            .get(OpKind::List, "users", Outcome::Success)
+            .map(|entry| entry.buckets.iter().sum::<u64>()),
+        Some(1)
+    );
+    assert!(metrics
+        .render()
+        .contains("op=WriteBatch table=users outcome=SchemaViolation count=1"));
+}
+
+#[test]
+fn administrative_writes_are_metered() {
+    let metrics = Arc::new(InMemoryMetrics::new());
+    let mut engine = InMemoryEngine::new();
+    engine.set_metrics(metrics.clone());
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .create_index("users", "by_name", &["name"])
+        .expect("index should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("insert should work");
+
+    engine
+        .update_schema("users", users_schema())
+        .expect("schema update should work");
+    engine
+        .update_schema_unchecked("users", users_schema())
+        .expect("schema update should work");
+    engine
+        .rebuild_index("users", "by_name")
+        .expect("rebuild should work");
+    assert!(engine.rebuild_index("users", "by_missing").is_err());
+    engine
+        .copy_table("users", "people", CopyTableOptions::default())
+        .expect("copy should work");
+    engine
+        .set_audit_log(true)
+        .expect("audit log should be enabled");
+    engine.prune_audit(0).expect("prune should work");
+    engine
+        .set_table_quota(
+            "users",
+            Some(Quota {
+                max_documents: Some(10),
+                max_bytes: None,
+            }),
+        )
+        .expect("quota should be set");
+    engine
+        .set_ttl(
+            "users",
+            Some(TtlPolicy {
+                field: "created_at".to_string(),
+                ttl_ms: 1_000,
+            }),
+        )
+        .expect("ttl should be set");
+    assert!(engine.set_ttl("missing", None).is_err());
+    let backup = engine.backup().expect("backup should work");
+    engine.restore(backup).expect("restore should work");
+    let snapshot = engine.export_table("users").expect("export should work");
+    engine.drop_table("users").expect("drop should work");
+    engine.import_table(snapshot).expect("import should work");
+
+    let count = |op, table: &str, outcome| {
+        metrics
+            .get(op, table, outcome)
+            .map_or(0, |entry| entry.count)
+    };
+    assert_eq!(count(OpKind::UpdateSchema, "users", Outcome::Success), 2);
+    assert_eq!(count(OpKind::RebuildIndex, "users", Outcome::Success), 1);
+    assert_eq!(count(OpKind::RebuildIndex, "users", Outcome::NotFound), 1);
+    assert_eq!(count(OpKind::CopyTable, "people", Outcome::Success), 1);
+    assert_eq!(count(OpKind::SetAuditLog, AUDIT_TABLE, Outcome::Success), 1);
+    assert_eq!(count(OpKind::PruneAudit, AUDIT_TABLE, Outcome::Success), 1);
+    assert_eq!(count(OpKind::Restore, "", Outcome::Success), 1);
+    assert_eq!(count(OpKind::SetQuota, "users", Outcome::Success), 1);
+    assert_eq!(count(OpKind::SetTtl, "users", Outcome::Success), 1);
+    assert_eq!(count(OpKind::SetTtl, "missing", Outcome::NotFound), 1);
+    assert_eq!(count(OpKind::Import, "users", Outcome::Success), 1);
+}
+
+#[test]
+fn read_only_health_blocks_writes_but_not_reads() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("insert should work");
+    engine
+        .create_index("users", "by_name", &["name"])
+        .expect("index should be created");
+    assert_eq!(engine.health(), &HealthState::Healthy);
+
+    // Stand-in for a persistence layer reacting to a failed WAL append.
+    engine.set_health(HealthState::ReadOnly("wal write failed".to_string()));
+
+    assert!(matches!(
+        engine.write_batch("users", &[put_user("u_2", "Ada")]),
+        Err(CoreError::ReadOnly(_))
+    ));
+    assert!(matches!(
+        engine.create_table("logs", Schema::default()),
+        Err(CoreError::ReadOnly(_))
+    ));
+    assert!(matches!(
+        engine.set_table_quota(
+            "users",
+            Some(Quota {
+                max_documents: Some(1),
+                max_bytes: None,
+            })
+        ),
+        Err(CoreError::ReadOnly(_))
+    ));
+    assert!(matches!(
+        engine.set_ttl(
+            "users",
+            Some(TtlPolicy {
+                field: "created_at".to_string(),
+                ttl_ms: 1_000,
+            })
+        ),
+        Err(CoreError::ReadOnly(_))
+    ));
+    assert!(matches!(
+        engine.rebuild_index("users", "by_name"),
+        Err(CoreError::ReadOnly(_))
+    ));
+    assert!(matches!(
+        engine.set_audit_log(true),
+        Err(CoreError::ReadOnly(_))
+    ));
+    assert!(!engine.contains_table(AUDIT_TABLE));
+    assert!(engine.get("users", "u_1").is_ok());
+    assert_eq!(engine.list_documents("users").expect("list").len(), 1);
+
+    engine.set_health(HealthState::Degraded("slow disk".to_string()));
+    engine
+        .write_batch("users", &[put_user("u_2", "Ada")])
+        .expect("degraded engines still accept writes");
+
+    engine.clear_health();
+    assert_eq!(engine.health(), &HealthState::Healthy);
+}
+
+#[test]
+fn ndjson_export_writes_one_object_per_line() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_2", "Ada"), put_user("u_1", "Lin")])
+        .expect("insert should work");
+
+    let mut output = Vec::new();
+    let written = engine
+        .export_table_ndjson("users", &mut output)
+        .expect("export should work");
+    assert_eq!(written, 2);
+
+    let lines: Vec<serde_json::Value> = String::from_utf8(output)
+        .expect("output should be utf-8")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("line should be json"))
+        .collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["_id"], "u_1");
+    assert_eq!(lines[0]["name"], "Lin");
+    assert_eq!(lines[1]["_id"], "u_2");
+    assert_eq!(lines[1]["_revision"], 1);
+}
+
+#[test]
+fn ndjson_import_round_trips_export_and_reports_bad_lines() {
+    let mut source = InMemoryEngine::new();
+    source
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    source
+        .write_batch("users", &[put_user("u_1", "Lin"), put_user("u_2", "Ada")])
+        .expect("insert should work");
+    let mut exported = Vec::new();
+    source
+        .export_table_ndjson("users", &mut exported)
+        .expect("export should work");
+
+    let mut target = InMemoryEngine::new();
+    target
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let imported = target
+        .import_ndjson("users", exported.as_slice())
+        .expect("import should work");
+    assert_eq!(imported, 2);
+    for (left, right) in source
+        .list_documents("users")
+        .expect("list")
+        .iter()
+        .zip(target.list_documents("users").expect("list"))
+    {
+        assert_eq!(left.id, right.id);
+        assert_eq!(left.fields, right.fields);
+    }
+
+    let malformed = "{\"name\":\"Grace\"}\n\n{\"name\": oops}\n";
+    let error = target
+        .import_ndjson("users", malformed.as_bytes())
+        .expect_err("malformed line should fail");
+    assert!(error.to_string().contains("line 3"));
+    assert_eq!(target.list_documents("users").expect("list").len(), 2);
+}
+
+#[test]
+fn conditional_writes_reject_stale_revisions() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let written = engine
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("insert should work");
+    let current = written[0].revision;
+
+    let WriteOperation::Put(renamed) = put_user("u_1", "Lin Chen") else {
+        unreachable!("put_user builds a put");
+    };
+    let updated = engine
+        .write_batch(
+            "users",
+            &[WriteOperation::PutIf {
+                document: renamed.clone(),
+                expected: current,
+            }],
+        )
+        .expect("matching revision should succeed");
+    let latest = updated[0].revision;
+
+    let stale = engine.write_batch(
+        "users",
+        &[
+            put_user("u_2", "Ada"),
+            WriteOperation::PutIf {
+                document: renamed,
+                expected: current,
+            },
+        ],
+    );
+    match stale.as_ref().map_err(CoreError::root) {
+        Err(CoreError::RevisionMismatch {
+            id,
+            expected,
+            actual,
+        }) => {
+            assert_eq!(id, "u_1");
+            assert_eq!(*expected, current);
+            assert_eq!(*actual, Some(latest));
+        }
+        other => panic!("expected revision mismatch, got {other:?}"),
+    }
+    assert!(engine.get("users", "u_2").is_err());
+
+    assert!(matches!(
+        engine
+            .write_batch(
+                "users",
+                &[WriteOperation::DeleteIf {
+                    id: "u_9".to_string(),
+                    expected: current,
+                }],
+            )
+            .unwrap_err()
+            .root(),
+        CoreError::RevisionMismatch { actual: None, .. }
+    ));
+    engine
+        .write_batch(
+            "users",
+            &[WriteOperation::DeleteIf {
+                id: "u_1".to_string(),
+                expected: latest,
+            }],
+        )
+        .expect("conditional delete should succeed");
+    assert!(engine.get("users", "u_1").is_err());
+
+    let WriteOperation::Put(unnamed) = put_unnamed("Bo") else {
+        unreachable!("put_unnamed builds a put");
+    };
+    let missing_id = engine
+        .write_batch(
+            "users",
+            &[WriteOperation::PutIf {
+                document: unnamed,
+                expected: latest,
+            }],
+        )
+        .expect_err("a conditional put needs an id");
+    assert!(matches!(
+        missing_id.root(),
+        CoreError::InvalidOperation(message) if message == "PutIf requires an explicit id"
+    ));
+    assert_eq!(engine.count_documents("users").expect("count"), 0);
+}
+
+#[test]
+fn secondary_index_tracks_writes_and_matches_mixed_type_keys() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("items", Schema::default())
+        .expect("table should be created");
+
+    let put = |id: &str, rank: serde_json::Value| {
+        let mut fields = BTreeMap::new();
+        fields.insert("rank".to_string(), rank);
+        WriteOperation::Put(NewDocument {
+            id: Some(id.to_string()),
+            fields,
+        })
+    };
+    engine
+        .write_batch(
+            "items",
+            &[
+                put("a", serde_json::json!(1)),
+                put("b", serde_json::json!("1")),
+                put("c", serde_json::json!(null)),
+            ],
+        )
+        .expect("seed should succeed");
+
+    engine
+        .create_index("items", "by_rank", &["rank"])
+        .expect("index should be created");
+    assert!(matches!(
+        engine.create_index("items", "by_rank", &["rank"]),
+        Err(CoreError::IndexAlreadyExists(_))
+    ));
+
+    engine
+        .write_batch(
+            "items",
+            &[
+                put("d", serde_json::json!(1.0)),
+                WriteOperation::Put(NewDocument {
+                    id: Some("e".to_string()),
+                    fields: BTreeMap::new(),
+                }),
+            ],
+        )
+        .expect("indexed writes should succeed");
+
+    assert_eq!(
+        query_ids(&engine, "items", "by_rank", &[serde_json::json!(1)]),
+        vec!["a", "d"]
+    );
+    assert_eq!(
+        query_ids(&engine, "items", "by_rank", &[serde_json::json!("1")]),
+        vec!["b"]
+    );
+    assert_eq!(
+        query_ids(&engine, "items", "by_rank", &[serde_json::json!(null)]),
+        vec!["c", "e"]
+    );
+
+    engine
+        .write_batch(
+            "items",
+            &[
+                put("a", serde_json::json!(2)),
+                WriteOperation::Delete("d".to_string()),
+            ],
+        )
+        .expect("update should succeed");
+    assert!(query_ids(&engine, "items", "by_rank", &[serde_json::json!(1)]).is_empty());
+    assert_eq!(
+        query_ids(&engine, "items", "by_rank", &[serde_json::json!(2)]),
+        vec!["a"]
+    );
+
+    let mut restored = InMemoryEngine::new();
+    restored
+        .import_table(engine.export_table("items").expect("export should succeed"))
+        .expect("import should succeed");
+    assert_eq!(
+        restored
+            .query_index("items", "by_rank", &[serde_json::json!(2)])
+            .expect("restored index should answer")
+            .len(),
+        1
+    );
+    assert!(matches!(
+        engine.query_index("items", "missing", &[serde_json::json!(1)]),
+        Err(CoreError::IndexNotFound(_))
+    ));
+}
+
+#[test]
+fn list_documents_page_walks_ids_after_cursor() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch(
+            "users",
+            &[
+                put_user("u_3", "Cy"),
+                put_user("u_1", "Ada"),
+                put_user("u_4", "Di"),
+                put_user("u_2", "Bo"),
+            ],
+        )
+        .expect("seed should succeed");
+
+    let page_ids = |after: Option<&str>| {
+        engine
+            .list_documents_page("users", after, 2)
+            .expect("page should load")
+            .into_iter()
+            .map(|document| document.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(page_ids(None), vec!["u_1", "u_2"]);
+    assert_eq!(page_ids(Some("u_2")), vec!["u_3", "u_4"]);
+    assert_eq!(page_ids(Some("u_25")), vec!["u_3", "u_4"]);
+    assert!(page_ids(Some("u_4")).is_empty());
+
+    assert_eq!(engine.count_documents("users").expect("count"), 4);
+    assert_eq!(
+        engine.list_documents("users").expect("list").len(),
+        engine.count_documents("users").expect("count")
+    );
+    assert!(matches!(
+        engine.count_documents("missing"),
+        Err(CoreError::TableNotFound { .. })
+    ));
+}
+
+#[test]
+fn sorted_index_orders_bucket_by_sort_field_then_id() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("tickets", Schema::default())
+        .expect("table should be created");
+    engine
+        .create_sorted_index("tickets", "by_status", &["status"], "priority")
+        .expect("index should be created");
+
+    let put = |id: &str, status: &str, priority: i64| {
+        let mut fields = BTreeMap::new();
+        fields.insert("status".to_string(), serde_json::json!(status));
+        fields.insert("priority".to_string(), serde_json::json!(priority));
+        WriteOperation::Put(NewDocument {
+            id: Some(id.to_string()),
+            fields,
+        })
+    };
+    engine
+        .write_batch(
+            "tickets",
+            &[
+                put("t_1", "open", 10),
+                put("t_2", "open", 2),
+                put("t_3", "closed", 1),
+                put("t_4", "open", 2),
+                put("t_5", "open", 7),
+            ],
+        )
+        .expect("seed should succeed");
+
+    let open_ids = |engine: &InMemoryEngine| {
+        query_ids(engine, "tickets", "by_status", &[serde_json::json!("open")])
+    };
+    assert_eq!(open_ids(&engine), vec!["t_2", "t_4", "t_5", "t_1"]);
+
+    engine
+        .write_batch("tickets", &[put("t_1", "open", 0)])
+        .expect("reprioritise should succeed");
+    assert_eq!(open_ids(&engine), vec!["t_1", "t_2", "t_4", "t_5"]);
+}
+
+#[test]
+fn drop_table_and_update_schema() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", Schema::default())
+        .expect("table should be created");
+    engine
+        .create_table("scratch", Schema::default())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Ada")])
+        .expect("seed should succeed");
+
+    engine.drop_table("scratch").expect("drop should succeed");
+    let names: Vec<String> = engine
+        .list_tables()
+        .into_iter()
+        .map(|state| state.name)
+        .collect();
+    assert_eq!(names, vec!["users".to_string()]);
+    assert!(matches!(
+        engine.drop_table("scratch"),
+        Err(CoreError::TableNotFound { .. })
+    ));
+
+    let mut strict = users_schema();
+    strict.fields.insert(
+        "email".to_string(),
+        SchemaField {
+            required: true,
+            field_type: SchemaType::String,
+        },
+    );
+    match engine.update_schema("users", strict.clone()) {
+        Err(CoreError::SchemaViolation {
+            document, field, ..
+        }) => {
+            assert_eq!(document.as_deref(), Some("u_1"));
+            assert_eq!(field.as_deref(), Some("email"));
+        }
+        other => panic!("expected schema violation, got {other:?}"),
+    }
+    assert!(engine
+        .write_batch("users", &[put_user("u_2", "Bo")])
+        .is_ok());
+
+    engine
+        .update_schema("users", users_schema())
+        .expect("compatible schema should apply");
+    assert!(engine
+        .write_batch(
+            "users",
+            &[WriteOperation::Put(NewDocument {
+                id: Some("u_3".to_string()),
+                fields: BTreeMap::new(),
+            })]
+        )
+        .is_err());
+
+    engine
+        .update_schema_unchecked("users", strict)
+        .expect("unchecked update should apply");
+    assert!(engine.get("users", "u_1").is_ok());
+    assert!(engine
+        .write_batch("users", &[put_user("u_4", "Di")])
+        .is_err());
+}
+
+#[test]
+fn entries_pair_ids_with_borrowed_documents() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_2", "Bo"), put_user("u_1", "Ada")])
+        .expect("seed should succeed");
+
+    let pairs: Vec<(String, String)> = engine
+        .iter_entries("users")
+        .expect("iteration should start")
+        .map(|(id, document)| (id.clone(), document.fields["name"].to_string()))
+        .collect();
+    assert_eq!(
+        pairs,
+        vec![
+            ("u_1".to_string(), "\"Ada\"".to_string()),
+            ("u_2".to_string(), "\"Bo\"".to_string()),
+        ]
+    );
+
+    let entries = engine.entries("users").expect("entries should load");
+    assert!(entries.iter().all(|(id, document)| **id == document.id));
+    assert!(engine.entries("missing").is_err());
+}
+
+#[test]
+fn global_id_uniqueness_rejects_ids_used_by_other_tables() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .create_table("admins", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("shared", "Ada")])
+        .expect("seed should succeed");
+
+    engine
+        .write_batch("admins", &[put_user("shared", "Bo")])
+        .expect("ids may repeat across tables by default");
+    engine
+        .write_batch("admins", &[WriteOperation::Delete("shared".to_string())])
+        .expect("cleanup should succeed");
+
+    engine.set_global_id_uniqueness(true);
+    match engine
+        .write_batch("admins", &[put_user("u_2", "Cy"), put_user("shared", "Bo")])
+        .as_ref()
+        .map_err(CoreError::root)
+    {
+        Err(CoreError::InvalidOperation(message)) => assert!(message.contains("users")),
+        other => panic!("expected collision, got {other:?}"),
+    }
+    assert!(engine.get("admins", "u_2").is_err());
+
+    engine
+        .write_batch("users", &[put_user("shared", "Ada Lovelace")])
+        .expect("overwriting in the owning table is allowed");
+    engine
+        .write_batch("admins", &[put_user("u_2", "Cy")])
+        .expect("fresh ids are accepted");
+}
+
+#[test]
+fn injected_sequential_ids_are_stable_per_table() {
+    let mut engine = InMemoryEngine::new();
+    engine.set_id_generator(Arc::new(SequentialIds::new()));
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .create_table("admins", users_schema())
+        .expect("table should be created");
+
+    let written = engine
+        .write_batch("users", &[put_unnamed("Ada"), put_unnamed("Bo")])
+        .expect("write should succeed");
+    let ids: Vec<&str> = written
+        .iter()
+        .map(|document| document.id.as_str())
+        .collect();
+    assert_eq!(ids, vec!["users:1", "users:2"]);
+
+    let admin = engine
+        .write_batch("admins", &[put_unnamed("Cy"), put_user("explicit", "Di")])
+        .expect("write should succeed");
+    assert_eq!(admin[0].id, "admins:1");
+    assert_eq!(admin[1].id, "explicit");
+    assert_eq!(
+        engine
+            .write_batch("users", &[put_unnamed("Eve")])
+            .expect("write should succeed")[0]
+            .id,
+        "users:3"
+    );
+}
+
+#[test]
+fn snapshots_mint_ids_and_record_metrics_independently() {
+    let metrics = Arc::new(InMemoryMetrics::new());
+    let mut engine = InMemoryEngine::new();
+    engine.set_id_generator(Arc::new(SequentialIds::new()));
+    engine.set_metrics(metrics.clone());
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+
+    engine
+        .write_batch("users", &[put_unnamed("Ada")])
+        .expect("write should succeed");
+
+    let mut fork = engine.snapshot();
+    let forked = fork
+        .write_batch("users", &[put_unnamed("Bo"), put_unnamed("Cy")])
+        .expect("fork write should succeed");
+    assert_eq!(forked[0].id, "users:2");
+    assert_eq!(forked[1].id, "users:3");
+    assert_eq!(
+        metrics
+            .get(OpKind::WriteBatch, "users", Outcome::Success)
+            .expect("parent write is recorded")
+            .count,
+        1
+    );
+
+    let next = engine
+        .write_batch("users", &[put_unnamed("Di")])
+        .expect("write should succeed");
+    assert_eq!(next[0].id, "users:2");
+}
+
+#[test]
+fn query_index_in_unions_keys_without_duplicates() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("tickets", Schema::default())
+        .expect("table should be created");
+    engine
+        .create_index("tickets", "by_status", &["status"])
+        .expect("index should be created");
+
+    let put = |id: &str, status: serde_json::Value| {
+        let mut fields = BTreeMap::new();
+        fields.insert("status".to_string(), status);
+        WriteOperation::Put(NewDocument {
+            id: Some(id.to_string()),
+            fields,
+        })
+    };
+    engine
+        .write_batch(
+            "tickets",
+            &[
+                put("t_1", serde_json::json!("open")),
+                put("t_2", serde_json::json!("blocked")),
+                put("t_3", serde_json::json!("done")),
+                put("t_4", serde_json::json!("open")),
+                put("t_5", serde_json::json!(2)),
+            ],
+        )
+        .expect("seed should succeed");
+
+    let open = [serde_json::json!("open")];
+    let blocked = [serde_json::json!("blocked")];
+    let pending = [serde_json::json!("pending")];
+    let two = [serde_json::json!(2)];
+    let two_float = [serde_json::json!(2.0)];
+    let ids: Vec<String> = engine
+        .query_index_in(
+            "tickets",
+            "by_status",
+            &[&open, &blocked, &pending, &open, &two, &two_float],
+        )
+        .expect("query should succeed")
+        .into_iter()
+        .map(|document| document.id)
+        .collect();
+    assert_eq!(ids, vec!["t_5", "t_2", "t_1", "t_4"]);
+
+    assert!(engine
+        .query_index_in("tickets", "by_status", &[])
+        .expect("empty query should succeed")
+        .is_empty());
+    assert!(engine
+        .query_index_in("tickets", "by_status", &[&[]])
+        .is_err());
+}
+
+#[test]
+fn query_index_range_respects_bounds_and_limit() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("scores", Schema::default())
+        .expect("table should be created");
+    engine
+        .create_index("scores", "by_points", &["points"])
+        .expect("index should be created");
+    let ops: Vec<WriteOperation> = (0..20)
+        .map(|points| {
+            let mut fields = BTreeMap::new();
+            fields.insert("points".to_string(), serde_json::json!(points % 10));
+            WriteOperation::Put(NewDocument {
+                id: Some(format!("s_{points:02}")),
+                fields,
+            })
+        })
+        .collect();
+    engine
+        .write_batch("scores", &ops)
+        .expect("seed should succeed");
+
+    let ids = |lower: Bound<&[serde_json::Value]>,
+               upper: Bound<&[serde_json::Value]>,
+               limit: Option<usize>| {
+        engine
+            .query_index_range("scores", "by_points", lower, upper, limit)
+            .expect("range should succeed")
+            .into_iter()
+            .map(|document| document.id)
+            .collect::<Vec<_>>()
+    };
+    let three = [serde_json::json!(3)];
+    let five = [serde_json::json!(5)];
+
+    assert_eq!(
+        ids(Bound::Included(&three), Bound::Excluded(&five), None),
+        vec!["s_03", "s_13", "s_04", "s_14"]
+    );
+    assert_eq!(
+        ids(Bound::Excluded(&three), Bound::Included(&five), Some(3)),
+        vec!["s_04", "s_14", "s_05"]
+    );
+    assert_eq!(
+        ids(Bound::Unbounded, Bound::Unbounded, Some(2)),
+        vec!["s_00", "s_10"]
+    );
+    assert!(ids(Bound::Included(&five), Bound::Included(&three), None).is_empty());
+    assert!(ids(Bound::Excluded(&five), Bound::Excluded(&five), None).is_empty());
+    assert!(engine
+        .query_index_range(
+            "scores",
+            "by_points",
+            Bound::Included(&[]),
+            Bound::Unbounded,
+            None
+        )
+        .is_err());
+}
+
+#[test]
+fn query_index_range_can_skip_null_keys() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("people", Schema::default())
+        .expect("table should be created");
+    engine
+        .create_index("people", "by_age", &["age"])
+        .expect("index should be created");
+    let ops: Vec<WriteOperation> = [("p_1", Some(30)), ("p_2", None), ("p_3", Some(40))]
+        .into_iter()
+        .map(|(id, age)| {
+            let mut fields = BTreeMap::new();
+            if let Some(age) = age {
+                fields.insert("age".to_string(), serde_json::json!(age));
+            }
+            WriteOperation::Put(NewDocument {
+                id: Some(id.to_string()),
+                fields,
+            })
+        })
+        .collect();
+    engine
+        .write_batch("people", &ops)
+        .expect("seed should succeed");
+
+    let below = [serde_json::json!(35)];
+    let ids = |skip_null: bool| {
+        engine
+            .query_index_range_with(
+                "people",
+                "by_age",
+                Bound::Unbounded,
+                Bound::Excluded(&below),
+                RangeOptions {
+                    skip_null,
+                    ..RangeOptions::default()
+                },
+            )
+            .expect("range should succeed")
+            .into_iter()
+            .map(|document| document.id)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(ids(false), vec!["p_2", "p_1"]);
+    assert_eq!(ids(true), vec!["p_1"]);
+}
+
+#[test]
+fn query_index_partial_treats_trailing_fields_as_wildcards() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("members", Schema::default())
+        .expect("table should be created");
+    engine
+        .create_index(
+            "members",
+            "by_tenant_team_email",
+            &["tenant", "team", "email"],
+        )
+        .expect("index should be created");
+
+    let put = |id: &str, tenant: &str, team: &str, email: &str| {
+        let mut fields = BTreeMap::new();
+        fields.insert("tenant".to_string(), serde_json::json!(tenant));
+        fields.insert("team".to_string(), serde_json::json!(team));
+        fields.insert("email".to_string(), serde_json::json!(email));
+        WriteOperation::Put(NewDocument {
+            id: Some(id.to_string()),
+            fields,
+        })
+    };
+    engine
+        .write_batch(
+            "members",
+            &[
+                put("m_1", "acme", "ops", "b@acme"),
+                put("m_2", "acme", "dev", "a@acme"),
+                put("m_3", "globex", "ops", "c@globex"),
+                put("m_4", "acme", "ops", "a@acme"),
+                put("m_5", "acmecorp", "ops", "d@acmecorp"),
+            ],
+        )
+        .expect("seed should succeed");
+
+    let ids = |prefix: &[serde_json::Value]| {
+        engine
+            .query_index_partial("members", "by_tenant_team_email", prefix)
+            .expect("query should succeed")
+            .into_iter()
+            .map(|document| document.id)
+            .collect::<Vec<_>>()
+    };
+    let acme = serde_json::json!("acme");
+    let ops = serde_json::json!("ops");
+
+    assert_eq!(ids(&[serde_json::json!("acme")]), vec!["m_2", "m_4", "m_1"]);
+    assert_eq!(ids(&[acme.clone(), ops.clone()]), vec!["m_4", "m_1"]);
+    assert_eq!(
+        ids(&[acme.clone(), ops.clone(), serde_json::json!("b@acme")]),
+        vec!["m_1"]
+    );
+    assert_eq!(ids(&[]).len(), 5);
+    assert!(engine
+        .query_index_partial(
+            "members",
+            "by_tenant_team_email",
+            &[
+                acme.clone(),
+                ops.clone(),
+                serde_json::json!("x"),
+                serde_json::json!("y")
+            ],
+        )
+        .is_err());
+}
+
+#[test]
+fn create_index_rejects_non_scalar_fields_unless_multikey() {
+    let mut schema = users_schema();
+    schema.fields.insert(
+        "tags".to_string(),
+        SchemaField {
+            required: false,
+            field_type: SchemaType::ArrayOf(Box::new(SchemaType::String)),
+        },
+    );
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", schema)
+        .expect("table should be created");
+    let mut fields = BTreeMap::new();
+    fields.insert("name".to_string(), serde_json::json!("Ada"));
+    fields.insert("tags".to_string(), serde_json::json!(["a", "b"]));
+    engine
+        .write_batch(
+            "users",
+            &[WriteOperation::Put(NewDocument {
+                id: Some("u_1".to_string()),
+                fields,
+            })],
+        )
+        .expect("seed should succeed");
+
+    match engine.create_index("users", "by_tags", &["tags"]) {
+        Err(CoreError::InvalidIndex { message, .. }) => assert!(message.contains("tags")),
+        other => panic!("expected invalid index, got {other:?}"),
+    }
+    engine
+        .create_index("users", "by_name_and_undeclared", &["name", "extra"])
+        .expect("scalar and undeclared fields are allowed");
+
+    let mut multikey = IndexDefinition::new("by_tags", &["tags"]);
+    multikey.multikey = true;
+    engine
+        .create_index_with("users", multikey)
+        .expect("multikey index should be allowed");
+    for tag in ["a", "b"] {
+        assert_eq!(
+            engine
+                .query_index("users", "by_tags", &[serde_json::json!(tag)])
+                .expect("query should succeed")
+                .len(),
+            1
+        );
+    }
+    assert!(engine
+        .query_index("users", "by_tags", &[serde_json::json!(["a", "b"])])
+        .expect("query should succeed")
+        .is_empty());
+    assert_eq!(
+        engine
+            .query_index_in(
+                "users",
+                "by_tags",
+                &[&[serde_json::json!("a")], &[serde_json::json!("b")]],
+            )
+            .expect("query should succeed")
+            .len(),
+        1
+    );
+
+    let tagged = |engine: &InMemoryEngine, tag: &str| {
+        query_ids(engine, "users", "by_tags", &[serde_json::json!(tag)])
+    };
+    engine
+        .array_push("users", "u_1", "tags", serde_json::json!("c"))
+        .expect("push should succeed");
+    assert_eq!(tagged(&engine, "c"), vec!["u_1".to_string()]);
+    engine
+        .array_remove("users", "u_1", "tags", serde_json::json!("a"))
+        .expect("remove should succeed");
+    assert!(tagged(&engine, "a").is_empty());
+    assert_eq!(tagged(&engine, "b"), vec!["u_1".to_string()]);
+    assert!(engine.verify_indexes("users").is_ok());
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+    age: u32,
+}
+
+#[test]
+fn typed_inserts_round_trip_and_grow_table_stats() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let mut previous = engine.table_stats("users").expect("stats should exist");
+    assert_eq!((previous.document_count, previous.estimated_bytes), (0, 0));
+
+    for (name, age) in [("Ada", 36), ("Grace", 45), ("Lin", 29)] {
+        let user = User {
+            name: name.to_string(),
+            age,
+        };
+        let written = engine
+            .insert_typed("users", &user)
+            .expect("typed insert should succeed");
+        let decoded: User = engine
+            .get("users", &written.id)
+            .expect("doc must exist")
+            .deserialize_fields()
+            .expect("fields should deserialize");
+        assert_eq!(decoded, user);
+
+        let current = engine.table_stats("users").expect("stats should exist");
+        assert_eq!(current.document_count, previous.document_count + 1);
+        assert!(current.estimated_bytes > previous.estimated_bytes);
+        previous = current;
+    }
+
+    assert!(engine.insert_typed("users", &"not an object").is_err());
+    assert!(engine.table_stats("missing").is_err());
+}
+
+#[test]
+fn patch_merges_fields_and_rolls_back_with_batch() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let inserted = engine
+        .insert_typed("users", &serde_json::json!({ "name": "Ada", "age": 36 }))
+        .expect("insert should succeed");
+    let patched = engine
+        .write_batch(
+            "users",
+            &[WriteOperation::Patch {
+                id: inserted.id.clone(),
+                fields: BTreeMap::from([("age".to_string(), serde_json::json!(37))]),
+            }],
+        )
+        .expect("patch should succeed");
+    assert_eq!(patched[0].fields["name"], "Ada");
+    assert_eq!(patched[0].fields["age"], 37);
+    assert_eq!(patched[0].revision.0, 2);
+
+    let bad_patch = WriteOperation::Patch {
+        id: "missing".to_string(),
+        fields: BTreeMap::new(),
+    };
+    let encoded = serde_json::to_string(&bad_patch).expect("op should serialize");
+    let decoded: WriteOperation = serde_json::from_str(&encoded).expect("op should parse");
+    assert_eq!(decoded, bad_patch);
+    assert!(engine
+        .write_batch("users", &[put_user("u_2", "Lin"), decoded])
+        .is_err());
+    assert!(engine.get("users", "u_2").is_err());
+
+    let invalid = engine.write_batch(
+        "users",
+        &[WriteOperation::Patch {
+            id: inserted.id,
+            fields: BTreeMap::from([("name".to_string(), serde_json::json!(false))]),
+        }],
+    );
+    assert!(invalid.is_err());
+}
+
+#[test]
+fn increment_and_array_updates_keep_indexes_current() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .create_index("users", "by_views", &["views"])
+        .expect("index should be created");
+    let mut by_tags = IndexDefinition::new("by_tags", &["tags"]);
+    by_tags.multikey = true;
+    engine
+        .create_index_with("users", by_tags)
+        .expect("index should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Ada")])
+        .expect("seed should succeed");
+
+    assert_eq!(engine.increment("users", "u_1", "views", 5).unwrap(), 5);
+    assert_eq!(engine.increment("users", "u_1", "views", -2).unwrap(), 3);
+    let views = |engine: &InMemoryEngine, count: i64| {
+        query_ids(engine, "users", "by_views", &[serde_json::json!(count)])
+    };
+    assert_eq!(views(&engine, 3), vec!["u_1"]);
+    assert!(views(&engine, 5).is_empty());
+    assert!(matches!(
+        engine
+            .increment("users", "u_1", "name", 1)
+            .unwrap_err()
+            .root(),
+        CoreError::InvalidOperation(_)
+    ));
+    assert!(matches!(
+        engine
+            .increment("users", "missing", "views", 1)
+            .unwrap_err()
+            .root(),
+        CoreError::DocumentNotFound { .. }
+    ));
+    assert_eq!(engine.get("users", "u_1").unwrap().fields["views"], 3);
+
+    let tagged = |engine: &InMemoryEngine, tag: &str| {
+        query_ids(engine, "users", "by_tags", &[serde_json::json!(tag)])
+    };
+    let pushed = engine
+        .array_push("users", "u_1", "tags", serde_json::json!("admin"))
+        .expect("push should create the array");
+    assert_eq!(pushed.fields["tags"], serde_json::json!(["admin"]));
+    assert_eq!(tagged(&engine, "admin"), vec!["u_1"]);
+    engine
+        .array_push("users", "u_1", "tags", serde_json::json!("ops"))
+        .expect("push should append");
+    let deduped = engine
+        .array_push("users", "u_1", "tags", serde_json::json!("admin"))
+        .expect("push of an existing element should succeed");
+    assert_eq!(deduped.fields["tags"], serde_json::json!(["admin", "ops"]));
+
+    let removed = engine
+        .array_remove("users", "u_1", "tags", serde_json::json!("admin"))
+        .expect("remove should succeed");
+    assert_eq!(removed.fields["tags"], serde_json::json!(["ops"]));
+    assert!(tagged(&engine, "admin").is_empty());
+    assert_eq!(tagged(&engine, "ops"), vec!["u_1"]);
+    assert!(engine.verify_indexes("users").is_ok());
+    assert!(matches!(
+        engine
+            .array_push("users", "u_1", "name", serde_json::json!("x"))
+            .unwrap_err()
+            .root(),
+        CoreError::InvalidOperation(_)
+    ));
+}
+
+#[test]
+fn validate_insert_matches_put_errors_without_writing() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+
+    let mut bad = BTreeMap::new();
+    bad.insert("name".to_string(), serde_json::json!(42));
+    let dry_run = engine
+        .validate_insert("users", &bad)
+        .expect_err("dry run should fail");
+    let write = engine
+        .write_batch(
+            "users",
+            &[WriteOperation::Put(NewDocument {
+                id: None,
+                fields: bad,
+            })],
+        )
+        .expect_err("write should fail");
+    assert_eq!(dry_run.to_string(), write.root().to_string());
+
+    let mut good = BTreeMap::new();
+    good.insert("name".to_string(), serde_json::json!("Ada"));
+    engine
+        .validate_insert("users", &good)
+        .expect("valid fields should pass");
+    assert_eq!(engine.count_documents("users").expect("count"), 0);
+    assert_eq!(engine.stats().current_revision, 0);
+    assert!(matches!(
+        engine.validate_insert("missing", &good),
+        Err(CoreError::TableNotFound { .. })
+    ));
+}
+
+#[test]
+fn best_index_for_prefers_plain_single_field_indexes() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", Schema::default())
+        .expect("table should be created");
+    assert_eq!(
+        engine.best_index_for("users", "name").expect("lookup"),
+        None
+    );
+
+    engine
+        .create_index("users", "by_name_age", &["name", "age"])
+        .expect("index should be created");
+    assert_eq!(
+        engine.best_index_for("users", "name").expect("lookup"),
+        None
+    );
+
+    let mut multikey = IndexDefinition::new("a_name_multikey", &["name"]);
+    multikey.multikey = true;
+    engine
+        .create_index_with("users", multikey)
+        .expect("index should be created");
+    assert_eq!(
+        engine.best_index_for("users", "name").expect("lookup"),
+        Some("a_name_multikey")
+    );
+
+    engine
+        .create_index("users", "z_name", &["name"])
+        .expect("index should be created");
+    engine
+        .create_index("users", "by_name", &["name"])
+        .expect("index should be created");
+    assert_eq!(
+        engine.best_index_for("users", "name").expect("lookup"),
+        Some("by_name")
+    );
+    assert!(engine.best_index_for("missing", "name").is_err());
+}
+
+#[test]
+fn user_fields_json_leaves_out_system_fields() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let written = engine
+        .write_batch("users", &[put_user("u_1", "Ada")])
+        .expect("write should succeed")
+        .remove(0);
+
+    let json = written.user_fields_json();
+    assert_eq!(json, serde_json::json!({ "name": "Ada" }));
+    assert!(json.get("_id").is_none() && json.get("_revision").is_none());
+    assert!(written.to_value().get("_id").is_some());
+}
+
+#[test]
+fn batch_and_import_errors_name_the_failing_item() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+
+    let mut bad = BTreeMap::new();
+    bad.insert("name".to_string(), serde_json::json!(42));
+    let error = engine
+        .write_batch(
+            "users",
+            &[
+                put_user("u_1", "Ada"),
+                WriteOperation::Put(NewDocument {
+                    id: Some("u_2".to_string()),
+                    fields: bad,
+                }),
+            ],
+        )
+        .expect_err("second put should fail");
+    match &error {
+        CoreError::WithContext {
+            op, id, item, line, ..
+        } => {
+            assert_eq!(
+                (*op, id.as_deref(), *item, *line),
+                ("put", Some("u_2"), 1, None)
+            );
+        }
+        other => panic!("expected batch context, got {other:?}"),
+    }
+    assert_eq!(error.error_code(), "SCHEMA_VIOLATION");
+    assert!(error
+        .to_string()
+        .starts_with("put #1 on users for document u_2: schema violation"));
+    assert!(engine
+        .list_documents("users")
+        .expect("listing should succeed")
+        .is_empty());
+
+    let input = "{\"_id\":\"u_3\",\"name\":\"Bo\"}\n\n{\"_id\":\"u_4\",\"name\":7}\n";
+    match engine.import_ndjson("users", input.as_bytes()) {
+        Err(CoreError::WithContext { item, line, .. }) => assert_eq!((item, line), (1, Some(3))),
+        other => panic!("expected import context, got {other:?}"),
+    }
+}
+
+#[test]
+fn default_strictness_guards_schemaless_tables() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("events", Schema::default())
+        .expect("table should be created");
+    let put = |fields: serde_json::Value| {
+        WriteOperation::Put(NewDocument {
+            id: None,
+            fields: serde_json::from_value(fields).expect("fields should be an object"),
+        })
+    };
+
+    let reserved = put(serde_json::json!({ "_owner": "ada", "kind": "click" }));
+    let wide = put(serde_json::json!({ "a": 1, "b": 2, "c": 3 }));
+    engine
+        .write_batch("events", &[reserved.clone(), wide.clone()])
+        .expect("schemaless tables accept anything by default");
+
+    engine.set_default_strict(true);
+    engine.set_default_max_fields(Some(2));
+    let rejected = engine
+        .write_batch("events", &[reserved])
+        .expect_err("reserved field should be rejected");
+    match rejected.root() {
+        CoreError::SchemaViolation { field, .. } => {
+            assert_eq!(field.as_deref(), Some("_owner"))
+        }
+        other => panic!("expected reserved field rejection, got {other:?}"),
+    }
+    assert_eq!(
+        engine
+            .write_batch("events", &[wide])
+            .expect_err("three fields exceed the cap")
+            .error_code(),
+        "SCHEMA_VIOLATION"
+    );
+    engine
+        .write_batch("events", &[put(serde_json::json!({ "a": 1, "b": 2 }))])
+        .expect("documents within the cap are accepted");
+
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch(
+            "users",
+            &[put(
+                serde_json::json!({ "name": "Ada", "_note": 1, "x": 2 }),
+            )],
+        )
+        .expect("tables with a schema are not affected");
+}
+
+#[test]
+fn find_first_and_any_stop_at_the_first_match() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let ops: Vec<WriteOperation> = (0..10)
+        .map(|n| put_user(&format!("u_{n}"), if n == 2 { "Ada" } else { "Bo" }))
+        .collect();
+    engine
+        .write_batch("users", &ops)
+        .expect("seed should succeed");
+
+    let mut calls = 0;
+    let found = engine
+        .find_first("users", |document| {
+            calls += 1;
+            document.fields["name"] == "Ada"
+        })
+        .expect("scan should succeed");
+    assert_eq!(found.map(|document| document.id.as_str()), Some("u_2"));
+    assert_eq!(calls, 3);
+
+    let mut calls = 0;
+    assert!(!engine
+        .any("users", |document| {
+            calls += 1;
+            document.fields["name"] == "Cy"
+        })
+        .expect("scan should succeed"));
+    assert_eq!(calls, 10);
+    assert!(engine.any("missing", |_| true).is_err());
+}
+
+#[test]
+fn audit_log_records_one_entry_per_changed_document() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_0", "Old")])
+        .expect("unaudited write should succeed");
+    engine
+        .set_audit_log(true)
+        .expect("audit log should be enabled");
+
+    engine.with_actor("admin", |engine| {
+        engine
+            .write_batch(
+                "users",
+                &[
+                    put_user("u_1", "Ada"),
+                    put_user("u_1", "Ada L"),
+                    put_user("u_2", "Bo"),
+                    WriteOperation::Delete("u_0".to_string()),
+                ],
+            )
+            .expect("batch should succeed");
+    });
+    let records = engine
+        .list_documents(AUDIT_TABLE)
+        .expect("audit table should be listable");
+    assert_eq!(records.len(), 3);
+    let mut by_document: BTreeMap<String, &BTreeMap<String, serde_json::Value>> = BTreeMap::new();
+    for record in &records {
+        assert_eq!(record.fields["actor"], "admin");
+        assert_eq!(record.fields["table"], "users");
+        by_document.insert(
+            record.fields["document"].as_str().unwrap().to_string(),
+            &record.fields,
+        );
+    }
+    assert_eq!(by_document["u_1"]["operation"], "insert");
+    assert_eq!(
+        by_document["u_1"]["after"],
+        serde_json::json!({ "name": "Ada L" })
+    );
+    assert!(!by_document["u_1"].contains_key("before"));
+    assert_eq!(by_document["u_0"]["operation"], "delete");
+    assert_eq!(
+        by_document["u_0"]["before"],
+        serde_json::json!({ "name": "Old" })
+    );
+
+    let failed = engine.write_batch(
+        "users",
+        &[
+            put_user("u_3", "Cy"),
+            WriteOperation::Delete("missing".to_string()),
+        ],
+    );
+    assert!(failed.is_err());
+    assert_eq!(engine.count_documents(AUDIT_TABLE).unwrap(), 3);
+
+    engine
+        .write_batch(
+            "users",
+            &[WriteOperation::Patch {
+                id: "u_2".to_string(),
+                fields: BTreeMap::from([("name".to_string(), serde_json::json!("Bea"))]),
+            }],
+        )
+        .expect("patch should succeed");
+    let update = engine
+        .find_first(AUDIT_TABLE, |record| record.fields["operation"] == "update")
+        .unwrap()
+        .expect("update should be audited");
+    assert!(!update.fields.contains_key("actor"));
+    assert_eq!(update.fields["before"], serde_json::json!({ "name": "Bo" }));
+    let newest = update.revision.0;
+
+    engine
+        .create_index(AUDIT_TABLE, "by_document", &["document"])
+        .expect("audit table should be indexable");
+    assert_eq!(
+        query_ids(
+            &engine,
+            AUDIT_TABLE,
+            "by_document",
+            &[serde_json::json!("u_2")]
+        )
+        .len(),
+        2
+    );
+
+    assert_eq!(engine.prune_audit(newest).unwrap(), 3);
+    assert_eq!(engine.count_documents(AUDIT_TABLE).unwrap(), 1);
+    assert_eq!(
+        query_ids(
+            &engine,
+            AUDIT_TABLE,
+            "by_document",
+            &[serde_json::json!("u_2")]
+        )
+        .len(),
+        1
+    );
+}
+
+#[test]
+fn audit_table_is_engine_maintained_and_survives_restore() {
+    let mut engine = InMemoryEngine::new();
+    engine.set_id_generator(Arc::new(SequentialIds::new()));
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    let backup = engine.backup().expect("backup should work");
+    engine
+        .set_audit_log(true)
+        .expect("audit log should be enabled");
+
+    let mut fields = BTreeMap::new();
+    fields.insert("name".to_string(), serde_json::json!("Ada"));
+    let written = engine
+        .write_batch(
+            "users",
+            &[WriteOperation::Put(NewDocument { id: None, fields })],
+        )
+        .expect("insert should work");
+    assert_eq!(written[0].id, "users:1");
+    let record = engine
+        .find_first(AUDIT_TABLE, |record| record.fields["document"] == "users:1")
+        .expect("audit table exists")
+        .expect("insert should be audited")
+        .clone();
+    assert_eq!(record.id, format!("{}:{}", AUDIT_TABLE, record.revision.0));
+
+    let user_write = engine
+        .write_batch(AUDIT_TABLE, &[WriteOperation::Delete(record.id.clone())])
+        .expect_err("audit records are not user-writable");
+    assert!(matches!(user_write.root(), CoreError::InvalidOperation(_)));
+    assert!(matches!(
+        engine.drop_table(AUDIT_TABLE),
+        Err(CoreError::InvalidOperation(_))
+    ));
+    assert_eq!(engine.count_documents(AUDIT_TABLE).unwrap(), 1);
+
+    engine.restore(backup).expect("restore should work");
+    assert!(engine.get("users", "users:1").is_err());
+    assert_eq!(
+        engine
+            .get(AUDIT_TABLE, &record.id)
+            .expect("live audit records survive a restore")
+            .fields,
+        record.fields
+    );
+}
+
+#[test]
+fn audit_table_cannot_be_seeded_before_auditing_starts() {
+    let mut engine = InMemoryEngine::new();
+    engine
+        .create_table("users", users_schema())
+        .expect("table should be created");
+    engine
+        .write_batch("users", &[put_user("u_1", "Lin")])
+        .expect("insert should work");
+
+    assert!(matches!(
+        engine.create_table(AUDIT_TABLE, Schema::default()),
+        Err(CoreError::InvalidOperation(_))
+    ));
+
+    let mut forged = engine.export_table("users").expect("export should work");
+    forged.name = AUDIT_TABLE.to_string();
+    assert!(matches!(
+        engine.import_table(forged.clone()),
+        Err(CoreError::InvalidOperation(_))
+    ));
+
+    let copied = engine
+        .copy_table("users", AUDIT_TABLE, CopyTableOptions::default())
+        .expect_err("copying into the audit table should fail");
+    assert!(matches!(copied.root(), CoreError::InvalidOperation(_)));
+    assert!(!engine.contains_table(AUDIT_TABLE));
+
+    let mut backup = engine.backup().expect("backup should work");
+    backup.tables.push(forged);
+    engine.restore(backup).expect("restore should work");
+    assert!(matches!(
+        engine.set_audit_log(true),
+        Err(CoreError::InvalidOperation(_))
+    ));
+    assert_eq!(engine.count_documents(AUDIT_TABLE).unwrap(), 1);
+    engine
+        .write_batch("users", &[put_user("u_2", "Ada")])
+        .expect("insert should work");
+    assert_eq!(engine.count_documents(AUDIT_TABLE).unwrap(), 1);
 }
@@ -0,0 +1,209 @@
+use core_db::index::compare_values;
+use core_db::{
+    Backup, Document, InMemoryEngine, IndexDefinition, NewDocument, Revision, Schema,
+    WriteOperation,
+};
+use proptest::prelude::*;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+const IDS: &[&str] = &["d0", "d1", "d2", "d3", "d4"];
+const FIELDS: &[&str] = &["a", "b", "c"];
+
+/// JSON values of bounded depth. Floats are quarter steps so they survive a
+/// text round-trip exactly.
+fn value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::Bool),
+        any::<i64>().prop_map(Value::from),
+        (-1000i32..1000).prop_map(|quarters| Value::from(f64::from(quarters) / 4.0)),
+        "[a-z]{0,6}".prop_map(Value::String),
+    ];
+    leaf.prop_recursive(3, 24, 4, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..4).prop_map(Value::Array),
+            prop::collection::btree_map("[a-c]{1,2}", inner, 0..4)
+                .prop_map(|map| Value::Object(map.into_iter().collect())),
+        ]
+    })
+}
+
+/// Integers and floats around 2^53, where an `f64` can no longer hold every
+/// integer exactly.
+fn large_number() -> impl Strategy<Value = Value> {
+    const BASE: i64 = 1 << 53;
+    prop_oneof![
+        (-4i64..4).prop_map(|offset| Value::from(BASE + offset)),
+        (-4i64..4).prop_map(|offset| Value::from((BASE + 2 * offset) as f64)),
+        (0i64..8).prop_map(|offset| Value::from(i64::MAX - offset)),
+        (-2i32..2)
+            .prop_map(|step| Value::from(9.223_372_036_854_776e18 + f64::from(step) * 2048.0)),
+    ]
+}
+
+fn id() -> impl Strategy<Value = String> {
+    prop::sample::select(IDS).prop_map(str::to_owned)
+}
+
+fn field() -> impl Strategy<Value = String> {
+    prop::sample::select(FIELDS).prop_map(str::to_owned)
+}
+
+fn fields() -> impl Strategy<Value = BTreeMap<String, Value>> {
+    prop::collection::btree_map(field(), value(), 0..3)
+}
+
+fn new_document() -> impl Strategy<Value = NewDocument> {
+    (id(), fields()).prop_map(|(id, fields)| NewDocument {
+        id: Some(id),
+        fields,
+    })
+}
+
+fn write_operation() -> impl Strategy<Value = WriteOperation> {
+    prop_oneof![
+        new_document().prop_map(WriteOperation::Put),
+        (id(), fields()).prop_map(|(id, fields)| WriteOperation::Patch { id, fields }),
+        id().prop_map(WriteOperation::Delete),
+        (id(), field(), -5i64..5).prop_map(|(id, field, delta)| WriteOperation::Increment {
+            id,
+            field,
+            delta
+        }),
+        (id(), field(), value()).prop_map(|(id, field, value)| WriteOperation::ArrayPush {
+            id,
+            field,
+            value
+        }),
+        (id(), field(), value()).prop_map(|(id, field, value)| WriteOperation::ArrayRemove {
+            id,
+            field,
+            value
+        }),
+        (new_document(), 0u64..40).prop_map(|(document, expected)| WriteOperation::PutIf {
+            document,
+            expected: Revision(expected),
+        }),
+        (id(), 0u64..40).prop_map(|(id, expected)| WriteOperation::DeleteIf {
+            id,
+            expected: Revision(expected),
+        }),
+    ]
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Batch(Vec<WriteOperation>),
+    CreateIndex { field: String, sorted: bool },
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    prop_oneof![
+        4 => prop::collection::vec(write_operation(), 1..4).prop_map(Step::Batch),
+        1 => (field(), any::<bool>()).prop_map(|(field, sorted)| Step::CreateIndex { field, sorted }),
+    ]
+}
+
+fn documents(engine: &InMemoryEngine) -> BTreeMap<String, Document> {
+    engine
+        .list_documents("docs")
+        .expect("table should exist")
+        .into_iter()
+        .map(|document| (document.id.clone(), document))
+        .collect()
+}
+
+/// The state a successful batch should leave: each touched id ends as the
+/// last document written for it, or absent if it was deleted afterwards.
+fn expected_after(
+    before: &BTreeMap<String, Document>,
+    ops: &[WriteOperation],
+    written: &[Document],
+) -> BTreeMap<String, Document> {
+    let mut expected = before.clone();
+    let mut written = written.iter();
+    for op in ops {
+        match op {
+            WriteOperation::Delete(id) | WriteOperation::DeleteIf { id, .. } => {
+                expected.remove(id);
+            }
+            _ => {
+                let document = written.next().expect("each write returns a document");
+                expected.insert(document.id.clone(), document.clone());
+            }
+        }
+    }
+    assert!(written.next().is_none(), "no extra documents are returned");
+    expected
+}
+
+proptest! {
+    #[test]
+    fn value_order_is_total(a in value(), b in value(), c in value()) {
+        prop_assert_eq!(compare_values(&a, &a), Ordering::Equal);
+        prop_assert_eq!(compare_values(&a, &b), compare_values(&b, &a).reverse());
+        if compare_values(&a, &b).is_le() && compare_values(&b, &c).is_le() {
+            prop_assert!(compare_values(&a, &c).is_le());
+        }
+    }
+
+    #[test]
+    fn mixed_large_numbers_sort_consistently(
+        mut numbers in prop::collection::vec(large_number(), 2..12),
+    ) {
+        numbers.sort_by(compare_values);
+        for (position, left) in numbers.iter().enumerate() {
+            for right in &numbers[position..] {
+                prop_assert!(compare_values(left, right).is_le(), "{} > {}", left, right);
+            }
+        }
+    }
+
+    #[test]
+    fn values_round_trip_through_json(value in value()) {
+        let text = serde_json::to_string(&value).expect("values serialize");
+        let decoded: Value = serde_json::from_str(&text).expect("values deserialize");
+        prop_assert_eq!(&decoded, &value);
+        prop_assert_eq!(compare_values(&decoded, &value), Ordering::Equal);
+    }
+
+    #[test]
+    fn random_operations_keep_indexes_and_writes_consistent(
+        steps in prop::collection::vec(step(), 1..24),
+    ) {
+        let mut engine = InMemoryEngine::new();
+        engine.create_table("docs", Schema::default()).expect("table should be created");
+
+        for step in steps {
+            match step {
+                Step::Batch(ops) => {
+                    let before = documents(&engine);
+                    match engine.write_batch("docs", &ops) {
+                        Ok(written) => {
+                            prop_assert_eq!(documents(&engine), expected_after(&before, &ops, &written));
+                        }
+                        Err(_) => prop_assert_eq!(documents(&engine), before),
+                    }
+                }
+                Step::CreateIndex { field, sorted } => {
+                    let mut definition = IndexDefinition::new(&format!("by_{field}_{sorted}"), &[&field]);
+                    definition.multikey = true;
+                    definition.sort_by = sorted.then(|| "b".to_string());
+                    // Repeating a definition is expected to fail; the state must not change.
+                    let _ = engine.create_index_with("docs", definition);
+                }
+            }
+            prop_assert!(engine.verify_indexes("docs").is_ok());
+        }
+
+        let bytes = engine.backup().expect("backup should succeed").to_bytes().expect("backup encodes");
+        let mut restored = InMemoryEngine::new();
+        restored
+            .restore(Backup::from_bytes(&bytes).expect("backup decodes"))
+            .expect("restore should succeed");
+        prop_assert_eq!(documents(&restored), documents(&engine));
+        prop_assert!(restored.verify_indexes("docs").is_ok());
+    }
+}
@@ -0,0 +1,176 @@
+#![cfg(feature = "tracing")]
+
+use core_db::{InMemoryEngine, NewDocument, Revision, Schema, WriteOperation};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// Span fields and warn-level event messages seen by `Capture`.
+#[derive(Debug, Default)]
+struct Captured {
+    spans: Vec<BTreeMap<String, String>>,
+    warnings: Vec<String>,
+}
+
+struct Capture {
+    captured: Arc<Mutex<Captured>>,
+}
+
+struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl Subscriber for Capture {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    // Span ids are 1-based positions in `Captured::spans`.
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = BTreeMap::new();
+        span.record(&mut FieldVisitor(&mut fields));
+        let mut captured = self.captured.lock().unwrap();
+        captured.spans.push(fields);
+        Id::from_u64(captured.spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut captured = self.captured.lock().unwrap();
+        let fields = &mut captured.spans[span.into_u64() as usize - 1];
+        values.record(&mut FieldVisitor(fields));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        if *event.metadata().level() == Level::WARN {
+            let mut fields = BTreeMap::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            let message = fields.remove("message").unwrap_or_default();
+            self.captured.lock().unwrap().warnings.push(message);
+        }
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+fn capture(run: impl FnOnce()) -> Captured {
+    let captured = Arc::new(Mutex::new(Captured::default()));
+    let subscriber = Capture {
+        captured: Arc::clone(&captured),
+    };
+    tracing::subscriber::with_default(subscriber, run);
+    let mut captured = captured.lock().unwrap();
+    std::mem::take(&mut *captured)
+}
+
+fn put(id: &str) -> WriteOperation {
+    WriteOperation::Put(NewDocument {
+        id: Some(id.to_string()),
+        fields: BTreeMap::from([("name".to_string(), serde_json::json!("Ada"))]),
+    })
+}
+
+#[test]
+fn writes_and_index_queries_emit_spans_with_fields() {
+    let captured = capture(|| {
+        let mut engine = InMemoryEngine::new();
+        engine.create_table("users", Schema::default()).unwrap();
+        engine.create_index("users", "by_name", &["name"]).unwrap();
+        engine.write_batch("users", &[put("u_1")]).unwrap();
+        engine
+            .query_index("users", "by_name", &[serde_json::json!("Ada")])
+            .unwrap();
+    });
+
+    let write = captured
+        .spans
+        .iter()
+        .find(|span| span.get("op").map(String::as_str) == Some("WriteBatch"))
+        .expect("write span should be recorded");
+    assert_eq!(write["table"], "users");
+    assert_eq!(write["documents"], "1");
+    assert_eq!(write["outcome"], "Success");
+    assert!(write.contains_key("elapsed_us"));
+
+    let query = captured
+        .spans
+        .iter()
+        .find(|span| span.get("op").map(String::as_str) == Some("IndexQuery"))
+        .expect("query span should be recorded");
+    assert_eq!(query["index"], "by_name");
+    assert_eq!(query["documents"], "1");
+}
+
+#[test]
+fn failed_conditional_write_warns_about_the_conflict() {
+    let captured = capture(|| {
+        let mut engine = InMemoryEngine::new();
+        engine.create_table("users", Schema::default()).unwrap();
+        let stale = WriteOperation::DeleteIf {
+            id: "u_1".to_string(),
+            expected: Revision(7),
+        };
+        assert!(engine.write_batch("users", &[stale]).is_err());
+    });
+
+    let write = captured
+        .spans
+        .iter()
+        .find(|span| span.get("op").map(String::as_str) == Some("WriteBatch"))
+        .expect("write span should be recorded");
+    assert_eq!(write["outcome"], "Conflict");
+    assert_eq!(captured.warnings, vec!["revision conflict".to_string()]);
+}
+
+#[test]
+fn administrative_writes_emit_spans() {
+    let captured = capture(|| {
+        let mut engine = InMemoryEngine::new();
+        engine.create_table("users", Schema::default()).unwrap();
+        engine.create_index("users", "by_name", &["name"]).unwrap();
+        engine.update_schema("users", Schema::default()).unwrap();
+        engine.rebuild_index("users", "by_name").unwrap();
+        engine.set_audit_log(true).unwrap();
+        engine.prune_audit(0).unwrap();
+        let backup = engine.backup().unwrap();
+        engine.restore(backup).unwrap();
+    });
+
+    let ops: Vec<&str> = captured
+        .spans
+        .iter()
+        .filter_map(|span| span.get("op").map(String::as_str))
+        .collect();
+    for op in [
+        "UpdateSchema",
+        "RebuildIndex",
+        "SetAuditLog",
+        "PruneAudit",
+        "Restore",
+    ] {
+        assert!(ops.contains(&op), "missing {op} span in {ops:?}");
+    }
+
+    let rebuild = captured
+        .spans
+        .iter()
+        .find(|span| span.get("op").map(String::as_str) == Some("RebuildIndex"))
+        .expect("rebuild span should be recorded");
+    assert_eq!(rebuild["index"], "by_name");
+    assert_eq!(rebuild["outcome"], "Success");
+}